@@ -0,0 +1,378 @@
+use std::ops::Deref;
+
+use crate::{Position, state::BoxInfo};
+
+/// the most cells along a side of the grid, so that a few huge boxes do not fill the memory
+const MAX_CELLS: usize = 256;
+/// the most cells a box is placed in, the bigger boxes are checked for every point and rect
+/// instead, so that many boxes over the whole grid do not fill the memory either
+const MAX_BOX_CELLS: usize = 64;
+
+/// The predefined boxes, with a grid over them built once, so that finding the boxes under the
+/// pointer or in a damaged rect does not scan all of them.
+#[derive(Debug, Default)]
+pub(crate) struct BoxSet {
+    boxes: Vec<BoxInfo>,
+    /// the left edge of the grid, the left edge of all the boxes
+    left: f64,
+    /// the top edge of the grid
+    top: f64,
+    cell_width: f64,
+    cell_height: f64,
+    columns: usize,
+    rows: usize,
+    /// the boxes of the cell `i` are `entries[starts[i]..starts[i + 1]]`, in input order
+    starts: Vec<usize>,
+    entries: Vec<usize>,
+    /// the boxes over more than [MAX_BOX_CELLS] cells, in input order
+    large: Vec<usize>,
+}
+
+impl Deref for BoxSet {
+    type Target = [BoxInfo];
+
+    fn deref(&self) -> &[BoxInfo] {
+        &self.boxes
+    }
+}
+
+impl BoxSet {
+    /// The set of `boxes`, without the ones with a NaN or infinite coordinate.
+    pub fn new(mut boxes: Vec<BoxInfo>) -> Self {
+        // they would stretch the grid without end
+        boxes.retain(|box_info| bounds(box_info).iter().all(|value| value.is_finite()));
+        if boxes.is_empty() {
+            return Self::default();
+        }
+        let [min_x, min_y, max_x, max_y] = boxes.iter().map(bounds).fold(
+            [f64::MAX, f64::MAX, f64::MIN, f64::MIN],
+            |[x1, y1, x2, y2], [bx1, by1, bx2, by2]| {
+                [x1.min(bx1), y1.min(by1), x2.max(bx2), y2.max(by2)]
+            },
+        );
+        let width = (max_x - min_x).max(1.0);
+        let height = (max_y - min_y).max(1.0);
+        // about one cell per box, square-ish
+        let count = boxes.len() as f64;
+        let columns = ((count * width / height).sqrt().ceil() as usize).clamp(1, MAX_CELLS);
+        let rows = ((count * height / width).sqrt().ceil() as usize).clamp(1, MAX_CELLS);
+        let mut set = Self {
+            left: min_x,
+            top: min_y,
+            cell_width: width / columns as f64,
+            cell_height: height / rows as f64,
+            columns,
+            rows,
+            starts: Vec::new(),
+            entries: Vec::new(),
+            large: Vec::new(),
+            boxes: Vec::new(),
+        };
+        let is_large = |set: &Self, box_info: &BoxInfo| {
+            set.span(bounds(box_info))
+                .is_some_and(|[column1, row1, column2, row2]| {
+                    (column2 - column1 + 1) * (row2 - row1 + 1) > MAX_BOX_CELLS
+                })
+        };
+
+        // count the boxes of each cell, then place them
+        let mut starts = vec![0; columns * rows + 1];
+        for (index, box_info) in boxes.iter().enumerate() {
+            if is_large(&set, box_info) {
+                set.large.push(index);
+                continue;
+            }
+            for cell in set.cells(bounds(box_info)) {
+                starts[cell + 1] += 1;
+            }
+        }
+        for cell in 0..columns * rows {
+            starts[cell + 1] += starts[cell];
+        }
+        let mut next = starts.clone();
+        let mut entries = vec![0; starts[columns * rows]];
+        for (index, box_info) in boxes.iter().enumerate() {
+            if is_large(&set, box_info) {
+                continue;
+            }
+            for cell in set.cells(bounds(box_info)) {
+                entries[next[cell]] = index;
+                next[cell] += 1;
+            }
+        }
+        set.starts = starts;
+        set.entries = entries;
+        set.boxes = boxes;
+        set
+    }
+
+    /// the boxes containing `point`, in input order
+    pub fn at(&self, point: Position<f64>) -> impl Iterator<Item = usize> + '_ {
+        let entries = match self.cells([point.x, point.y, point.x, point.y]).next() {
+            Some(cell) => &self.entries[self.starts[cell]..self.starts[cell + 1]],
+            None => &[],
+        };
+        merged(entries, &self.large).filter(move |index| {
+            let [x1, y1, x2, y2] = bounds(&self.boxes[*index]);
+            point.x >= x1 && point.x <= x2 && point.y >= y1 && point.y <= y2
+        })
+    }
+
+    /// Add the boxes intersecting `rect`, as `[x1, y1, x2, y2]`, to `out`. `out` is left in input
+    /// order, without duplicates.
+    pub fn within(&self, rect: [f64; 4], out: &mut Vec<usize>) {
+        let intersects = |index: &usize| {
+            let [x1, y1, x2, y2] = bounds(&self.boxes[*index]);
+            x1 <= rect[2] && x2 >= rect[0] && y1 <= rect[3] && y2 >= rect[1]
+        };
+        for cell in self.cells(rect) {
+            out.extend(
+                self.entries[self.starts[cell]..self.starts[cell + 1]]
+                    .iter()
+                    .copied()
+                    .filter(intersects),
+            );
+        }
+        out.extend(self.large.iter().copied().filter(intersects));
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    /// the cells overlapping `[x1, y1, x2, y2]`
+    fn cells(&self, rect: [f64; 4]) -> impl Iterator<Item = usize> + '_ {
+        self.span(rect)
+            .into_iter()
+            .flat_map(move |[column1, row1, column2, row2]| {
+                (row1..=row2).flat_map(move |row| {
+                    (column1..=column2).map(move |column| row * self.columns + column)
+                })
+            })
+    }
+
+    /// the first and last cells overlapping `[x1, y1, x2, y2]`, as `[column1, row1, column2, row2]`,
+    /// `None` when it misses the grid
+    fn span(&self, [x1, y1, x2, y2]: [f64; 4]) -> Option<[usize; 4]> {
+        let column = |x: f64| {
+            (((x - self.left) / self.cell_width).floor().max(0.0) as usize)
+                .min(self.columns.saturating_sub(1))
+        };
+        let row = |y: f64| {
+            (((y - self.top) / self.cell_height).floor().max(0.0) as usize)
+                .min(self.rows.saturating_sub(1))
+        };
+        let grid_x2 = self.left + self.cell_width * self.columns as f64;
+        let grid_y2 = self.top + self.cell_height * self.rows as f64;
+        // nothing when the rect misses the grid
+        let overlaps =
+            self.columns > 0 && x2 >= self.left && y2 >= self.top && x1 <= grid_x2 && y1 <= grid_y2;
+        overlaps.then(|| [column(x1), row(y1), column(x2), row(y2)])
+    }
+}
+
+/// the indices of two sorted lists, in order
+fn merged<'a>(first: &'a [usize], second: &'a [usize]) -> impl Iterator<Item = usize> + 'a {
+    let (mut first, mut second) = (first.iter().peekable(), second.iter().peekable());
+    std::iter::from_fn(move || {
+        let from_first = match (first.peek(), second.peek()) {
+            (Some(a), Some(b)) => a <= b,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        match from_first {
+            true => first.next(),
+            false => second.next(),
+        }
+        .copied()
+    })
+}
+
+/// the box as `[x1, y1, x2, y2]`, whichever way its size goes
+fn bounds(box_info: &BoxInfo) -> [f64; 4] {
+    [
+        box_info.start_x.min(box_info.end_x),
+        box_info.start_y.min(box_info.end_y),
+        box_info.start_x.max(box_info.end_x),
+        box_info.start_y.max(box_info.end_y),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a small deterministic generator, a linear congruential one
+    struct Lcg(u64);
+
+    impl Lcg {
+        /// a value in `[low, high)`, in quarters so that edges coincide often
+        fn next(&mut self, low: i32, high: i32) -> f64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let quarters = ((high - low) * 4) as u64;
+            low as f64 + ((self.0 >> 33) % quarters) as f64 / 4.0
+        }
+    }
+
+    fn box_info(x: f64, y: f64, width: f64, height: f64) -> BoxInfo {
+        BoxInfo::new(x, y, x + width, y + height)
+    }
+
+    fn random_boxes(random: &mut Lcg, count: usize) -> Vec<BoxInfo> {
+        (0..count)
+            .map(|_| {
+                box_info(
+                    random.next(-500, 500),
+                    random.next(-500, 500),
+                    // a negative size goes up or left from the start
+                    random.next(-200, 200),
+                    random.next(-200, 200),
+                )
+            })
+            .collect()
+    }
+
+    /// the points worth checking: random ones, the corners and edges of the boxes and of the cells
+    fn probes(set: &BoxSet, random: &mut Lcg) -> Vec<Position<f64>> {
+        let mut xs: Vec<f64> = (0..20).map(|_| random.next(-800, 800)).collect();
+        let mut ys: Vec<f64> = (0..20).map(|_| random.next(-800, 800)).collect();
+        for box_info in set.iter() {
+            let [x1, y1, x2, y2] = bounds(box_info);
+            xs.extend([x1, x2, (x1 + x2) / 2.0]);
+            ys.extend([y1, y2, (y1 + y2) / 2.0]);
+        }
+        xs.extend((0..=set.columns).map(|column| set.left + set.cell_width * column as f64));
+        ys.extend((0..=set.rows).map(|row| set.top + set.cell_height * row as f64));
+        xs.iter()
+            .zip(ys.iter().cycle().skip(3))
+            .chain(xs.iter().zip(ys.iter().rev().cycle()))
+            .map(|(&x, &y)| Position { x, y })
+            .collect()
+    }
+
+    fn linear_at(boxes: &[BoxInfo], point: Position<f64>) -> Vec<usize> {
+        (0..boxes.len())
+            .filter(|index| {
+                let [x1, y1, x2, y2] = bounds(&boxes[*index]);
+                point.x >= x1 && point.x <= x2 && point.y >= y1 && point.y <= y2
+            })
+            .collect()
+    }
+
+    fn linear_within(boxes: &[BoxInfo], rect: [f64; 4]) -> Vec<usize> {
+        (0..boxes.len())
+            .filter(|index| {
+                let [x1, y1, x2, y2] = bounds(&boxes[*index]);
+                x1 <= rect[2] && x2 >= rect[0] && y1 <= rect[3] && y2 >= rect[1]
+            })
+            .collect()
+    }
+
+    fn check(boxes: Vec<BoxInfo>, random: &mut Lcg) {
+        let set = BoxSet::new(boxes.clone());
+        for point in probes(&set, random) {
+            assert_eq!(
+                set.at(point).collect::<Vec<_>>(),
+                linear_at(&boxes, point),
+                "at {point:?}"
+            );
+            // a rect from the probe, of any size including none
+            let (width, height) = (random.next(0, 300), random.next(0, 300));
+            for rect in [
+                [point.x, point.y, point.x, point.y],
+                [point.x, point.y, point.x + width, point.y + height],
+                [point.x - width, point.y - height, point.x, point.y],
+            ] {
+                let mut out = Vec::new();
+                set.within(rect, &mut out);
+                assert_eq!(out, linear_within(&boxes, rect), "within {rect:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_a_linear_scan() {
+        let mut random = Lcg(0x5eed);
+        for count in [1, 2, 3, 10, 64, 500] {
+            for _ in 0..4 {
+                let boxes = random_boxes(&mut random, count);
+                check(boxes, &mut random);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_a_linear_scan_with_a_box_over_the_grid() {
+        let mut random = Lcg(42);
+        for count in [0, 5, 100] {
+            let mut boxes = random_boxes(&mut random, count);
+            boxes.insert(count / 2, box_info(-800.0, -800.0, 1600.0, 1600.0));
+            check(boxes, &mut random);
+        }
+    }
+
+    #[test]
+    fn matches_a_linear_scan_on_cell_edges() {
+        // a row of touching boxes, each edge shared by two boxes and on a cell edge
+        let boxes: Vec<BoxInfo> = (0..16)
+            .map(|index| box_info(index as f64 * 10.0, 0.0, 10.0, 10.0))
+            .chain([
+                box_info(160.0, 10.0, -160.0, -10.0),
+                box_info(35.0, 5.0, 0.0, 0.0),
+            ])
+            .collect();
+        check(boxes, &mut Lcg(7));
+        let set = BoxSet::new(
+            (0..16)
+                .map(|index| box_info(index as f64 * 10.0, 0.0, 10.0, 10.0))
+                .collect(),
+        );
+        assert_eq!(
+            set.at(Position { x: 30.0, y: 5.0 }).collect::<Vec<_>>(),
+            [2, 3]
+        );
+    }
+
+    #[test]
+    fn empty_set() {
+        let set = BoxSet::new(Vec::new());
+        assert!(set.is_empty());
+        assert_eq!(set.at(Position { x: 0.0, y: 0.0 }).count(), 0);
+        let mut out = Vec::new();
+        set.within([-1e9, -1e9, 1e9, 1e9], &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn matches_a_linear_scan_with_many_boxes_over_the_grid() {
+        let mut random = Lcg(0xb16);
+        let mut boxes = random_boxes(&mut random, 200);
+        for index in (0..200).step_by(2) {
+            boxes.insert(index, box_info(-600.0, -600.0, 1200.0, 1200.0));
+        }
+        let set = BoxSet::new(boxes.clone());
+        assert_eq!(set.large.len(), 100);
+        assert!(set.entries.len() <= 200 * MAX_BOX_CELLS);
+        check(boxes, &mut random);
+    }
+
+    #[test]
+    fn boxes_without_finite_coordinates_are_dropped() {
+        let boxes = vec![
+            box_info(0.0, 0.0, 10.0, 10.0),
+            box_info(f64::NAN, 0.0, 10.0, 10.0),
+            box_info(0.0, 0.0, f64::INFINITY, 10.0),
+            box_info(f64::NEG_INFINITY, 0.0, 10.0, 10.0),
+            box_info(5.0, 5.0, 10.0, 10.0),
+        ];
+        let set = BoxSet::new(boxes);
+        assert_eq!(set.len(), 2);
+        assert_eq!((set.left, set.top), (0.0, 0.0));
+        assert_eq!(
+            set.at(Position { x: 7.0, y: 7.0 }).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert!(BoxSet::new(vec![box_info(f64::NAN, 0.0, 1.0, 1.0)]).is_empty());
+    }
+}
@@ -1,9 +1,12 @@
+use std::{fs::File, os::fd::OwnedFd};
+
 use crate::{
     Position, Size,
-    state::{self, LayerSurfaceInfo, WaysipState},
+    state::{self, Direction, LayerSurfaceInfo, WaysipState},
 };
+use memmap2::MmapOptions;
 use wayland_client::{
-    Connection, Dispatch, Proxy, WEnum, delegate_noop,
+    Connection, Dispatch, Proxy, WEnum, delegate_noop, event_created_child,
     globals::GlobalListContents,
     protocol::{
         wl_buffer::{self, WlBuffer},
@@ -26,10 +29,18 @@ use wayland_protocols::{
         xdg_output::zv1::client::{zxdg_output_manager_v1::ZxdgOutputManagerV1, zxdg_output_v1},
     },
 };
-use wayland_protocols_wlr::layer_shell::v1::client::{
-    zwlr_layer_shell_v1::ZwlrLayerShellV1,
-    zwlr_layer_surface_v1::{self},
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        zwlr_layer_surface_v1::{self},
+    },
+    output_management::v1::client::{
+        zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+        zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+        zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+    },
 };
+use xkbcommon::xkb;
 
 impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for WaysipState {
     fn event(
@@ -116,7 +127,8 @@ impl Dispatch<wl_registry::WlRegistry, ()> for state::WaysipState {
         };
 
         if interface == wl_output::WlOutput::interface().name {
-            let output = proxy.bind::<wl_output::WlOutput, _, _>(name, version, qh, ());
+            // version 4 is needed for the name and description events
+            let output = proxy.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qh, ());
             state.wloutput_infos.push(state::WlOutputInfo::new(output));
         }
     }
@@ -144,8 +156,48 @@ impl Dispatch<wl_output::WlOutput, ()> for state::WaysipState {
             wl_output::Event::Description { description } => {
                 output.description = description;
             }
-            wl_output::Event::Mode { width, height, .. } => {
-                output.size = Size { width, height };
+            wl_output::Event::Geometry {
+                physical_width,
+                physical_height,
+                subpixel,
+                make,
+                model,
+                transform,
+                ..
+            } => {
+                output.physical_size = Size {
+                    width: physical_width,
+                    height: physical_height,
+                };
+                if let WEnum::Value(subpixel) = subpixel {
+                    output.subpixel = subpixel;
+                }
+                output.make = make;
+                output.model = model;
+                if let WEnum::Value(transform) = transform {
+                    output.transform = transform;
+                }
+            }
+            wl_output::Event::Mode {
+                flags,
+                width,
+                height,
+                refresh,
+            } => {
+                let flags = match flags {
+                    WEnum::Value(flags) => flags,
+                    WEnum::Unknown(_) => wl_output::Mode::Current,
+                };
+                // NOTE: only the current mode describes the output, the others are just
+                // advertised
+                if flags.contains(wl_output::Mode::Current) {
+                    output.size = Size { width, height };
+                    output.refresh = refresh;
+                    output.preferred_mode = flags.contains(wl_output::Mode::Preferred);
+                }
+            }
+            wl_output::Event::Scale { factor } => {
+                output.scale = factor;
             }
 
             _ => (),
@@ -153,6 +205,85 @@ impl Dispatch<wl_output::WlOutput, ()> for state::WaysipState {
     }
 }
 
+impl Dispatch<ZwlrOutputManagerV1, ()> for state::WaysipState {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrOutputManagerV1,
+        event: <ZwlrOutputManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &wayland_client::QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_manager_v1::Event::Head { head } => {
+                state.output_heads.push(state::OutputHeadInfo::new(head));
+            }
+            zwlr_output_manager_v1::Event::Done { .. } => {
+                state.apply_output_heads();
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(state::WaysipState, ZwlrOutputManagerV1, [
+        zwlr_output_manager_v1::EVT_HEAD_OPCODE => (ZwlrOutputHeadV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputHeadV1, ()> for state::WaysipState {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputHeadV1,
+        event: <ZwlrOutputHeadV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &wayland_client::QueueHandle<Self>,
+    ) {
+        if let zwlr_output_head_v1::Event::Finished = event {
+            state.output_heads.retain(|info| info.head != *proxy);
+            // before version 3 the head can't be released
+            if proxy.version() >= 3 {
+                proxy.release();
+            }
+            return;
+        }
+        let Some(info) = state
+            .output_heads
+            .iter_mut()
+            .find(|info| info.head == *proxy)
+        else {
+            return;
+        };
+        match event {
+            zwlr_output_head_v1::Event::Name { name } => info.name = name,
+            zwlr_output_head_v1::Event::Scale { scale } => info.scale = Some(scale),
+            zwlr_output_head_v1::Event::Enabled { enabled } => info.enabled = Some(enabled != 0),
+            _ => {}
+        }
+    }
+
+    event_created_child!(state::WaysipState, ZwlrOutputHeadV1, [
+        zwlr_output_head_v1::EVT_MODE_OPCODE => (ZwlrOutputModeV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputModeV1, ()> for state::WaysipState {
+    fn event(
+        _state: &mut Self,
+        proxy: &ZwlrOutputModeV1,
+        event: <ZwlrOutputModeV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &wayland_client::QueueHandle<Self>,
+    ) {
+        // the current mode is already known from wl_output, the modes are only released
+        match event {
+            zwlr_output_mode_v1::Event::Finished if proxy.version() >= 3 => proxy.release(),
+            _ => {}
+        }
+    }
+}
+
 impl Dispatch<xdg_wm_base::XdgWmBase, ()> for state::WaysipState {
     fn event(
         _state: &mut Self,
@@ -191,6 +322,21 @@ impl Dispatch<wl_seat::WlSeat, ()> for state::WaysipState {
     }
 }
 
+/// evdev keycodes, as sent by wl_keyboard
+const KEY_ESC: u32 = 1;
+const KEY_TAB: u32 = 15;
+const KEY_ENTER: u32 = 28;
+const KEY_F1: u32 = 59;
+const KEY_KPENTER: u32 = 96;
+const KEY_UP: u32 = 103;
+const KEY_LEFT: u32 = 105;
+const KEY_RIGHT: u32 = 106;
+const KEY_DOWN: u32 = 108;
+/// the shift modifier in the keymaps of xkbcommon, before the keymap is read
+const SHIFT_MASK: u32 = 1;
+/// xkb keycodes are evdev keycodes plus 8
+const XKB_KEYCODE_OFFSET: u32 = 8;
+
 impl Dispatch<wl_keyboard::WlKeyboard, ()> for state::WaysipState {
     fn event(
         state: &mut Self,
@@ -200,12 +346,129 @@ impl Dispatch<wl_keyboard::WlKeyboard, ()> for state::WaysipState {
         _conn: &Connection,
         _qhandle: &wayland_client::QueueHandle<Self>,
     ) {
-        if let wl_keyboard::Event::Key { key: 1, .. } = event {
-            state.running = false;
+        if let wl_keyboard::Event::Key { .. } = event {
+            state.last_input = std::time::Instant::now();
+        }
+        match event {
+            wl_keyboard::Event::Keymap {
+                format: WEnum::Value(wl_keyboard::KeymapFormat::XkbV1),
+                fd,
+                size,
+            } => state.keymap = Keymap::from_fd(fd, size),
+            wl_keyboard::Event::Key { key: KEY_ESC, .. } => state.running = false,
+            wl_keyboard::Event::Key {
+                state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+                ..
+            } if state.is_identify() => state.running = false,
+            wl_keyboard::Event::Key {
+                key,
+                state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+                ..
+            } if key == KEY_F1 || key_char(state, key) == Some('?') => state.toggle_help(),
+            wl_keyboard::Event::Key {
+                key,
+                state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+                ..
+            } if state.is_picking_boxes() => box_key(state, key),
+            wl_keyboard::Event::Key {
+                key: KEY_TAB,
+                state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+                ..
+            } if state.is_selecting_area() => state.step_aspect(if state.shift { -1 } else { 1 }),
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                state.shift = match state.keymap.as_mut() {
+                    Some(keymap) => {
+                        keymap.set_modifiers(mods_depressed, mods_latched, mods_locked, group)
+                    }
+                    None => mods_depressed & SHIFT_MASK != 0,
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// move through the predefined boxes with the keyboard
+fn box_key(state: &mut WaysipState, key: u32) {
+    match key {
+        KEY_TAB => state.step_box(if state.shift { -1 } else { 1 }),
+        KEY_ENTER | KEY_KPENTER => state.confirm_box(),
+        KEY_LEFT => state.move_box(Direction::Left),
+        KEY_RIGHT => state.move_box(Direction::Right),
+        KEY_UP => state.move_box(Direction::Up),
+        KEY_DOWN => state.move_box(Direction::Down),
+        _ => {
+            if let Some(c) = key_char(state, key) {
+                state.type_box_label(c);
+            }
+        }
+    }
+}
+
+/// The keymap of the seat, with the state of its modifiers.
+pub(crate) struct Keymap(xkb::State);
+
+impl Keymap {
+    /// the keymap sent by the compositor, `None` when it cannot be read
+    fn from_fd(fd: OwnedFd, size: u32) -> Option<Self> {
+        // SAFETY: the compositor does not change the keymap it sends, which is mapped private as
+        // wl_keyboard requires
+        let map = unsafe {
+            MmapOptions::new()
+                .len(size as usize)
+                .map_copy_read_only(&File::from(fd))
+        }
+        .ok()?;
+        // the text ends with a nul, which is a syntax error to libxkbcommon
+        let text = std::str::from_utf8(&map).ok()?.trim_end_matches('\0');
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        xkb::Keymap::new_from_string(
+            &context,
+            text.to_string(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .map(|keymap| Self(xkb::State::new(&keymap)))
+    }
+
+    /// update the modifiers, and return whether shift is held
+    fn set_modifiers(&mut self, depressed: u32, latched: u32, locked: u32, group: u32) -> bool {
+        self.0.update_mask(depressed, latched, locked, 0, 0, group);
+        self.0
+            .mod_name_is_active(xkb::MOD_NAME_SHIFT, xkb::STATE_MODS_EFFECTIVE)
+    }
+
+    /// the character typed by a key with the current modifiers, none for the keys without one,
+    /// like the arrows or Enter
+    fn key_char(&self, key: u32) -> Option<char> {
+        let text = self
+            .0
+            .key_get_utf8(xkb::Keycode::new(key + XKB_KEYCODE_OFFSET));
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !c.is_control() => Some(c),
+            _ => None,
         }
     }
 }
 
+impl std::fmt::Debug for Keymap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Keymap")
+    }
+}
+
+/// the character typed by a key, none before the keymap is read
+fn key_char(state: &WaysipState, key: u32) -> Option<char> {
+    state.keymap.as_ref()?.key_char(key)
+}
+
 impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
     fn event(
         dispatch_state: &mut Self,
@@ -215,7 +478,21 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
         _conn: &Connection,
         qh: &wayland_client::QueueHandle<Self>,
     ) {
+        dispatch_state.last_input = std::time::Instant::now();
         match event {
+            wl_pointer::Event::Button { .. } if dispatch_state.is_identify() => {
+                dispatch_state.running = false;
+            }
+            // the fixed-size area is placed where it is drawn, the release changes nothing
+            wl_pointer::Event::Button {
+                state: WEnum::Value(wl_pointer::ButtonState::Pressed),
+                ..
+            } if dispatch_state.is_fixed_size() => {
+                dispatch_state.update_stamp();
+                dispatch_state.running = false;
+                dispatch_state.commit();
+            }
+            wl_pointer::Event::Button { .. } if dispatch_state.is_fixed_size() => {}
             wl_pointer::Event::Button { state, .. } => {
                 match state {
                     WEnum::Value(wl_pointer::ButtonState::Pressed) => {
@@ -224,12 +501,16 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                             dispatch_state.mouse_press_time = Some(std::time::Instant::now());
                         }
 
-                        if !dispatch_state.is_predefined_boxes() {
+                        if dispatch_state.is_boxes_or_dimensions() {
+                            // the box under the pointer stays selected until the pointer moves
+                            dispatch_state.press_pos = Some(dispatch_state.current_pos);
+                        } else if !dispatch_state.is_predefined_boxes() {
                             dispatch_state.set_start_pos(dispatch_state.current_pos);
                         }
                         if !dispatch_state.is_area()
                             && !dispatch_state.is_predefined_boxes()
                             && !dispatch_state.is_dimensions_or_output()
+                            && !dispatch_state.is_boxes_or_dimensions()
                         {
                             dispatch_state.end_pos = Some(dispatch_state.current_pos);
                             dispatch_state.running = false;
@@ -279,8 +560,23 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                                 // Behave like dimensions selection (-d)
                                 dispatch_state.effective_selection_type =
                                     Some(crate::state::SelectionType::Area);
-                                dispatch_state.end_pos = Some(dispatch_state.current_pos);
+                                dispatch_state.update_area();
+                            }
+                        } else if dispatch_state.is_boxes_or_dimensions() {
+                            dispatch_state.press_pos = None;
+                            if dispatch_state.is_effective_area() {
+                                dispatch_state.update_area();
+                            } else if dispatch_state.is_over_box() {
+                                dispatch_state.effective_selection_type =
+                                    Some(crate::state::SelectionType::PredefinedBoxes);
+                            } else {
+                                // a click outside the boxes selects nothing
+                                dispatch_state.commit();
+                                return;
                             }
+                        } else if dispatch_state.is_area() {
+                            // the area keeps its aspect ratio and its constraints on release
+                            dispatch_state.update_area();
                         } else if !dispatch_state.is_predefined_boxes() {
                             dispatch_state.end_pos = Some(dispatch_state.current_pos);
                         }
@@ -339,6 +635,8 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                     cursor_surface.commit();
                 }
 
+                // the fixed-size area shows up under the pointer right away
+                dispatch_state.update_stamp();
                 dispatch_state.commit();
             }
             wl_pointer::Event::Motion {
@@ -355,33 +653,13 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                     y: surface_y + start_y as f64,
                 };
                 dispatch_state.end_pos = None;
+                dispatch_state.update_drag();
 
                 // NOTE:  when it is area, we just use one click to get the position, so we
                 // need to know the end_pos immediately. so even the start_pos is not decided, we
                 // still need an end_pos
-                if dispatch_state.is_area() || dispatch_state.is_dimensions_or_output() {
-                    if let Some(ratio) = dispatch_state.aspect_ratio {
-                        let width_rel = ratio.0;
-                        let height_rel = ratio.1;
-                        let start_pos = dispatch_state
-                            .start_pos
-                            .unwrap_or(dispatch_state.current_pos);
-                        let width = dispatch_state.current_pos.x - start_pos.x;
-                        let height = dispatch_state.current_pos.y - start_pos.y;
-                        if width_rel / height_rel > width / height {
-                            dispatch_state.end_pos = Some(Position {
-                                x: start_pos.x + height * width_rel / height_rel,
-                                y: start_pos.y + height,
-                            });
-                        } else {
-                            dispatch_state.end_pos = Some(Position {
-                                x: start_pos.x + width,
-                                y: start_pos.y + width * height_rel / width_rel,
-                            });
-                        }
-                    } else {
-                        dispatch_state.end_pos = Some(dispatch_state.current_pos);
-                    }
+                if dispatch_state.is_selecting_area() {
+                    dispatch_state.update_area();
 
                     let now = std::time::Instant::now();
                     if now.duration_since(dispatch_state.last_redraw)
@@ -390,30 +668,17 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                         dispatch_state.commit();
                         dispatch_state.last_redraw = now;
                     }
-                } else if dispatch_state.is_predefined_boxes() {
-                    let current_pos = dispatch_state.current_pos;
-                    if let Some(box_info) = dispatch_state
-                        .predefined_boxes
-                        .clone()
-                        .unwrap()
-                        .iter()
-                        .find(|box_info| {
-                            current_pos.x >= box_info.start_x
-                                && current_pos.x <= box_info.end_x
-                                && current_pos.y >= box_info.start_y
-                                && current_pos.y <= box_info.end_y
-                        })
+                } else if dispatch_state.is_fixed_size() {
+                    dispatch_state.update_stamp();
+                    let now = std::time::Instant::now();
+                    if now.duration_since(dispatch_state.last_redraw)
+                        >= std::time::Duration::from_millis(8)
                     {
-                        dispatch_state.end_pos = Some(dispatch_state.current_pos);
-                        dispatch_state.set_start_pos(Position {
-                            x: box_info.start_x,
-                            y: box_info.start_y,
-                        });
-                        dispatch_state.end_pos = Some(Position {
-                            x: box_info.end_x,
-                            y: box_info.end_y,
-                        });
+                        dispatch_state.commit();
+                        dispatch_state.last_redraw = now;
                     }
+                } else if dispatch_state.is_picking_boxes() {
+                    dispatch_state.update_hovered_box();
                     let now = std::time::Instant::now();
                     if now.duration_since(dispatch_state.last_redraw)
                         >= std::time::Duration::from_millis(20)
@@ -422,8 +687,40 @@ impl Dispatch<wl_pointer::WlPointer, ()> for state::WaysipState {
                         dispatch_state.commit();
                         dispatch_state.last_redraw = now;
                     }
+                } else if dispatch_state.has_guides() {
+                    let now = std::time::Instant::now();
+                    if now.duration_since(dispatch_state.last_redraw)
+                        >= std::time::Duration::from_millis(8)
+                    {
+                        dispatch_state.commit();
+                        dispatch_state.last_redraw = now;
+                    }
                 }
             }
+            // the wheel cycles through the boxes under the pointer
+            wl_pointer::Event::Axis {
+                axis: WEnum::Value(wl_pointer::Axis::VerticalScroll),
+                value,
+                ..
+            } if dispatch_state.is_picking_boxes() => {
+                dispatch_state.scroll_boxes(value);
+            }
+            // and goes through the aspect presets while dragging
+            wl_pointer::Event::Axis {
+                axis: WEnum::Value(wl_pointer::Axis::VerticalScroll),
+                value,
+                ..
+            } if dispatch_state.is_selecting_area() => {
+                dispatch_state.scroll_aspect(value);
+            }
+            // and scales the fixed-size area
+            wl_pointer::Event::Axis {
+                axis: WEnum::Value(wl_pointer::Axis::VerticalScroll),
+                value,
+                ..
+            } if dispatch_state.is_fixed_size() => {
+                dispatch_state.scroll_stamp(value);
+            }
             _ => {}
         }
     }
@@ -443,6 +740,9 @@ impl Dispatch<WlCallback, usize> for state::WaysipState {
                 return;
             }
             state.redraw();
+            if state.is_animating() {
+                state.commit();
+            }
         }
     }
 }
@@ -482,3 +782,263 @@ delegate_noop!(WaysipState: ignore ZxdgOutputManagerV1);
 
 delegate_noop!(WaysipState: ignore WpCursorShapeManagerV1);
 delegate_noop!(WaysipState: ignore WpCursorShapeDeviceV1);
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use wayland_client::{QueueHandle, protocol::wl_output::WlOutput};
+
+    use super::*;
+    use crate::SelectionType;
+
+    /// a connection to a socket nobody reads, the requests are only buffered
+    fn connection() -> (Connection, QueueHandle<WaysipState>, UnixStream) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(client).unwrap();
+        let qh = conn.new_event_queue::<WaysipState>().handle();
+        (conn, qh, server)
+    }
+
+    fn bind<I: Proxy + 'static>(conn: &Connection, qh: &QueueHandle<WaysipState>, name: u32) -> I
+    where
+        WaysipState: Dispatch<I, ()>,
+    {
+        conn.display()
+            .get_registry(qh, ())
+            .bind::<I, _, _>(name, 1, qh, ())
+    }
+
+    fn mode(flags: wl_output::Mode, width: i32, refresh: i32) -> wl_output::Event {
+        wl_output::Event::Mode {
+            flags: WEnum::Value(flags),
+            width,
+            height: width / 2,
+            refresh,
+        }
+    }
+
+    #[test]
+    fn only_the_current_mode_describes_the_output() {
+        let (conn, qh, _server) = connection();
+        let output: WlOutput = bind(&conn, &qh, 1);
+        let mut state = WaysipState::new(SelectionType::Area);
+        state
+            .wloutput_infos
+            .push(state::WlOutputInfo::new(output.clone()));
+        for event in [
+            mode(wl_output::Mode::Preferred, 3840, 60000),
+            mode(wl_output::Mode::Current, 1920, 144000),
+            mode(wl_output::Mode::empty(), 1280, 75000),
+        ] {
+            WaysipState::event(&mut state, &output, event, &(), &conn, &qh);
+        }
+        let info = &state.wloutput_infos[0];
+        assert_eq!((info.size.width, info.size.height), (1920, 960));
+        assert_eq!(info.refresh, 144000);
+        assert!(!info.preferred_mode);
+
+        let event = mode(
+            wl_output::Mode::Current | wl_output::Mode::Preferred,
+            2560,
+            60000,
+        );
+        WaysipState::event(&mut state, &output, event, &(), &conn, &qh);
+        let info = &state.wloutput_infos[0];
+        assert_eq!((info.size.width, info.refresh), (2560, 60000));
+        assert!(info.preferred_mode);
+    }
+
+    #[test]
+    fn heads_apply_to_the_output_with_their_name_on_done() {
+        let (conn, qh, _server) = connection();
+        let manager: ZwlrOutputManagerV1 = bind(&conn, &qh, 1);
+        let mut state = WaysipState::new(SelectionType::Area);
+        for (name, index) in [("DP-1", 2), ("HDMI-A-1", 3)] {
+            let mut info = state::WlOutputInfo::new(bind(&conn, &qh, index));
+            info.name = name.to_string();
+            state.wloutput_infos.push(info);
+        }
+        // the heads are created by the compositor, an inert proxy stands for one
+        let head = ZwlrOutputHeadV1::inert(conn.backend().downgrade());
+        WaysipState::event(
+            &mut state,
+            &manager,
+            zwlr_output_manager_v1::Event::Head { head: head.clone() },
+            &(),
+            &conn,
+            &qh,
+        );
+        for event in [
+            zwlr_output_head_v1::Event::Name {
+                name: "HDMI-A-1".to_string(),
+            },
+            zwlr_output_head_v1::Event::Scale { scale: 1.25 },
+            zwlr_output_head_v1::Event::Enabled { enabled: 0 },
+        ] {
+            WaysipState::event(&mut state, &head, event, &(), &conn, &qh);
+        }
+        // nothing is applied before done
+        assert_eq!(state.wloutput_infos[1].fractional_scale, None);
+
+        let done = zwlr_output_manager_v1::Event::Done { serial: 1 };
+        WaysipState::event(&mut state, &manager, done, &(), &conn, &qh);
+        assert_eq!(state.wloutput_infos[0].fractional_scale, None);
+        assert_eq!(state.wloutput_infos[0].enabled, None);
+        assert_eq!(state.wloutput_infos[1].fractional_scale, Some(1.25));
+        assert_eq!(state.wloutput_infos[1].enabled, Some(false));
+
+        WaysipState::event(
+            &mut state,
+            &head,
+            zwlr_output_head_v1::Event::Finished,
+            &(),
+            &conn,
+            &qh,
+        );
+        assert!(state.output_heads.is_empty());
+    }
+
+    /// a message on the wire, as sent by the compositor
+    fn message(object: u32, opcode: u16, args: &[u32]) -> Vec<u8> {
+        let size = 8 + 4 * args.len() as u32;
+        [object, size << 16 | opcode as u32]
+            .iter()
+            .chain(args)
+            .flat_map(|word| word.to_ne_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn finished_heads_and_modes_are_released_from_version_3() {
+        const HEAD: u32 = 0xff00_0000;
+        const MODE: u32 = 0xff00_0001;
+        for version in [1, 3, 4] {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            let conn = Connection::from_socket(client).unwrap();
+            let mut queue = conn.new_event_queue::<WaysipState>();
+            let qh = queue.handle();
+            let manager: ZwlrOutputManagerV1 =
+                conn.display()
+                    .get_registry(&qh, ())
+                    .bind(1, version, &qh, ());
+            let manager_id = manager.id().protocol_id();
+            let events = [
+                message(manager_id, zwlr_output_manager_v1::EVT_HEAD_OPCODE, &[HEAD]),
+                message(HEAD, zwlr_output_head_v1::EVT_MODE_OPCODE, &[MODE]),
+                message(MODE, zwlr_output_mode_v1::EVT_FINISHED_OPCODE, &[]),
+                message(HEAD, zwlr_output_head_v1::EVT_FINISHED_OPCODE, &[]),
+            ]
+            .concat();
+            server.write_all(&events).unwrap();
+
+            let mut state = WaysipState::new(SelectionType::Area);
+            queue.blocking_dispatch(&mut state).unwrap();
+            assert!(state.output_heads.is_empty());
+            conn.flush().unwrap();
+            drop(conn);
+            drop(queue);
+            drop(manager);
+            let mut requests = Vec::new();
+            server.read_to_end(&mut requests).unwrap();
+
+            let released = |object| {
+                let release = message(object, 0, &[]);
+                requests
+                    .windows(release.len())
+                    .any(|window| window == release)
+            };
+            assert_eq!(released(HEAD), version >= 3, "head, version {version}");
+            assert_eq!(released(MODE), version >= 3, "mode, version {version}");
+        }
+    }
+
+    /// the keymap of `layout`, in a file as sent by the compositor
+    fn keymap(layout: &str) -> wl_keyboard::Event {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "",
+            "",
+            layout,
+            "",
+            None,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        let mut text = keymap
+            .get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)
+            .into_bytes();
+        text.push(0);
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&text).unwrap();
+        wl_keyboard::Event::Keymap {
+            format: WEnum::Value(wl_keyboard::KeymapFormat::XkbV1),
+            fd: file.into(),
+            size: text.len() as u32,
+        }
+    }
+
+    fn press(key: u32) -> wl_keyboard::Event {
+        wl_keyboard::Event::Key {
+            serial: 0,
+            time: 0,
+            key,
+            state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+        }
+    }
+
+    fn modifiers(mods_depressed: u32) -> wl_keyboard::Event {
+        wl_keyboard::Event::Modifiers {
+            serial: 0,
+            mods_depressed,
+            mods_latched: 0,
+            mods_locked: 0,
+            group: 0,
+        }
+    }
+
+    #[test]
+    fn keys_are_read_through_the_keymap_of_the_compositor() {
+        let (conn, qh, _server) = connection();
+        let keyboard: wl_keyboard::WlKeyboard = bind(&conn, &qh, 1);
+        let mut state = WaysipState::new(SelectionType::Area);
+        let send = |state: &mut WaysipState, event| {
+            WaysipState::event(state, &keyboard, event, &(), &conn, &qh)
+        };
+        const KEY_Q: u32 = 16;
+        const KEY_SLASH: u32 = 53;
+        assert_eq!(key_char(&state, KEY_Q), None);
+
+        send(&mut state, keymap("us"));
+        assert_eq!(key_char(&state, KEY_Q), Some('q'));
+        assert_eq!(key_char(&state, KEY_ENTER), None);
+        send(&mut state, modifiers(SHIFT_MASK));
+        assert!(state.shift);
+        assert_eq!(key_char(&state, KEY_Q), Some('Q'));
+        send(&mut state, press(KEY_SLASH));
+        assert!(state.help_visible);
+        send(&mut state, modifiers(0));
+        assert!(!state.shift);
+        send(&mut state, press(KEY_SLASH));
+        assert!(state.help_visible);
+
+        send(&mut state, keymap("fr"));
+        assert_eq!(key_char(&state, KEY_Q), Some('a'));
+        assert_eq!(key_char(&state, 2), Some('&'));
+    }
+
+    #[test]
+    fn key_presses_are_input() {
+        let (conn, qh, _server) = connection();
+        let keyboard: wl_keyboard::WlKeyboard = bind(&conn, &qh, 1);
+        let mut state = WaysipState::new(SelectionType::Area);
+        let idle = std::time::Duration::from_secs(60);
+        state.last_input = std::time::Instant::now() - idle;
+        WaysipState::event(&mut state, &keyboard, press(KEY_LEFT), &(), &conn, &qh);
+        assert!(state.last_input.elapsed() < idle);
+    }
+}
@@ -6,6 +6,8 @@ use wayland_client::{DispatchError, globals::BindError};
 /// 2. failed in queue
 /// 3. failed when protocol not supported
 /// 4. Error returned when the cursor cannot be obtained from the compositor
+/// 5. failed when drawing the offscreen preview
+/// 6. the bounds name an output or a predefined box that does not exist
 
 #[derive(Error, Debug)]
 pub enum WaySipError {
@@ -17,6 +19,10 @@ pub enum WaySipError {
     NotSupportedProtocol(BindError),
     #[error("Cannot get cursor")]
     CursorThemeFetchFailed,
+    #[error("Failed to draw the preview: {0}")]
+    PreviewFailed(String),
+    #[error("Invalid bounds: {0}")]
+    InvalidBounds(String),
 }
 
 #[derive(Debug, Error)]
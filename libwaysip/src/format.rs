@@ -0,0 +1,925 @@
+//! Format templates, shared by the output of the selection and the labels drawn on the overlay.
+//!
+//! A template is text with placeholders:
+//! - `%x`, `%W`, `%px`, ...: the single letter placeholders, see `--help`.
+//! - `%{expr}` / `%{expr:spec}`: a named field or arithmetic on fields, like `%{w/2}`, formatted by
+//!   `spec` = `[<|>|^][0][width][.precision]`, like `%{x:05}` or `%{nx:.3}`.
+//! - `%{?field:then|else}`: `then` when the field is non-zero / non-empty, else `else`, like
+//!   `%{?screen:output %o|area}`. `%{?!field:...}` negates the condition.
+//! - `%%`, `\n`, `\\`, and `\` before any other character to escape it, like `\|` or `\}`.
+
+use thiserror::Error;
+use wayland_client::protocol::wl_output::Transform;
+
+use crate::{AreaInfo, Position, Size};
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("Unknown placeholder `%{0}`")]
+    UnknownPlaceholder(String),
+    #[error("Unknown field `{0}`")]
+    UnknownField(String),
+    #[error("Field `{0}` is text and cannot be used in arithmetic")]
+    TextInArithmetic(String),
+    #[error("Invalid expression `{0}`")]
+    InvalidExpression(String),
+    #[error("Invalid format spec `{0}`, expected `[<|>|^][0][width][.precision]`")]
+    InvalidSpec(String),
+    #[error("Unterminated `%{{` placeholder")]
+    Unterminated,
+    #[error("Dangling `%` at the end of the format")]
+    DanglingPercent,
+}
+
+/// A value a template can print.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Text(s) => !s.is_empty(),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(i) => *i as f64,
+            Value::Float(f) => *f,
+            Value::Text(_) => f64::NAN,
+        }
+    }
+}
+
+macro_rules! fields {
+    ($($field:ident => $name:literal, $letters:literal, $text:literal;)*) => {
+        /// Everything a template can refer to.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Field {
+            $($field,)*
+        }
+
+        impl Field {
+            /// `(field, name, single letter placeholders, is text)`
+            const ALL: &[(Field, &'static str, &'static str, bool)] = &[
+                $((Field::$field, $name, $letters, $text),)*
+            ];
+        }
+    };
+}
+
+fields! {
+    X => "x", "x", false;
+    Y => "y", "y", false;
+    Width => "width", "w", false;
+    Height => "height", "h", false;
+    RelX => "rel_x", "X", false;
+    RelY => "rel_y", "Y", false;
+    RelWidth => "rel_width", "W", false;
+    RelHeight => "rel_height", "H", false;
+    PhysX => "px", "", false;
+    PhysY => "py", "", false;
+    PhysWidth => "pw", "", false;
+    PhysHeight => "ph", "", false;
+    NormX => "nx", "", false;
+    NormY => "ny", "", false;
+    NormWidth => "nw", "", false;
+    NormHeight => "nh", "", false;
+    OutputX => "output_x", "", false;
+    OutputY => "output_y", "", false;
+    OutputWidth => "output_width", "", false;
+    OutputHeight => "output_height", "", false;
+    ModeWidth => "mode_width", "L", false;
+    ModeHeight => "mode_height", "T", false;
+    MmWidth => "mm_width", "", false;
+    MmHeight => "mm_height", "", false;
+    Scale => "scale", "s", false;
+    Refresh => "refresh", "r", false;
+    Screen => "screen", "", false;
+    Area => "area", "", false;
+    Output => "output", "ol", true;
+    Description => "description", "d", true;
+    Make => "make", "m", true;
+    Model => "model", "M", true;
+    Transform => "transform", "t", true;
+    PhysicalSize => "physical_size", "P", true;
+    Label => "label", "b", true;
+}
+
+impl Field {
+    fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(_, _, letters, _)| letters.contains(letter))
+            .map(|(field, ..)| *field)
+    }
+
+    /// Names, and the single letters as names, so `%{w}` is `%w`.
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(_, field_name, letters, _)| {
+                *field_name == name || (name.chars().count() == 1 && letters.contains(name))
+            })
+            .map(|(field, ..)| *field)
+    }
+
+    fn is_text(self) -> bool {
+        Self::ALL
+            .iter()
+            .any(|(field, _, _, text)| *field == self && *text)
+    }
+}
+
+/// Provides the values of the fields when rendering.
+pub trait FieldSource {
+    fn value(&self, field: Field) -> Value;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(Value),
+    Field(Field),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, source: &impl FieldSource) -> Value {
+        match self {
+            Expr::Number(value) => value.clone(),
+            Expr::Field(field) => source.value(*field),
+            Expr::Neg(expr) => match expr.eval(source) {
+                Value::Int(i) => Value::Int(-i),
+                other => Value::Float(-other.as_f64()),
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                match (op, lhs.eval(source), rhs.eval(source)) {
+                    (Op::Add, Value::Int(a), Value::Int(b)) => Value::Int(a.saturating_add(b)),
+                    (Op::Sub, Value::Int(a), Value::Int(b)) => Value::Int(a.saturating_sub(b)),
+                    (Op::Mul, Value::Int(a), Value::Int(b)) => Value::Int(a.saturating_mul(b)),
+                    // keep exact divisions integral, so `%{w/2}` of an even width stays an integer
+                    (Op::Div, Value::Int(a), Value::Int(b)) if b != 0 && a % b == 0 => {
+                        Value::Int(a / b)
+                    }
+                    (op, a, b) => {
+                        let (a, b) = (a.as_f64(), b.as_f64());
+                        Value::Float(match op {
+                            Op::Add => a + b,
+                            Op::Sub => a - b,
+                            Op::Mul => a * b,
+                            Op::Div => a / b,
+                        })
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Align {
+    #[default]
+    Auto,
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Spec {
+    align: Align,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Result<Self, FormatError> {
+        let invalid = || FormatError::InvalidSpec(spec.to_string());
+        let mut result = Spec::default();
+        let mut rest = spec;
+        if let Some(align) = rest.chars().next() {
+            result.align = match align {
+                '<' => Align::Left,
+                '>' => Align::Right,
+                '^' => Align::Center,
+                _ => Align::Auto,
+            };
+            if !matches!(result.align, Align::Auto) {
+                rest = &rest[1..];
+            }
+        }
+        if let Some(stripped) = rest.strip_prefix('0') {
+            result.zero = true;
+            rest = stripped;
+        }
+        let (width, precision) = match rest.split_once('.') {
+            Some((width, precision)) => (width, Some(precision)),
+            None => (rest, None),
+        };
+        if !width.is_empty() {
+            result.width = width.parse().map_err(|_| invalid())?;
+        }
+        if let Some(precision) = precision {
+            result.precision = Some(precision.parse().map_err(|_| invalid())?);
+        }
+        Ok(result)
+    }
+
+    fn apply(&self, value: &Value) -> String {
+        let width = self.width;
+        let (text, numeric) = match (value, self.precision) {
+            (Value::Text(s), _) => (s.clone(), false),
+            (Value::Int(i), None) if self.zero => (format!("{i:0width$}"), true),
+            (Value::Int(i), None) => (i.to_string(), true),
+            (value, Some(precision)) if self.zero => {
+                (format!("{:0width$.precision$}", value.as_f64()), true)
+            }
+            (value, Some(precision)) => (format!("{:.precision$}", value.as_f64()), true),
+            (value, None) if self.zero => (format!("{:0width$}", value.as_f64()), true),
+            (value, None) => (value.as_f64().to_string(), true),
+        };
+        let align = match self.align {
+            Align::Auto if numeric => Align::Right,
+            Align::Auto => Align::Left,
+            align => align,
+        };
+        match align {
+            Align::Left => format!("{text:<width$}"),
+            Align::Center => format!("{text:^width$}"),
+            _ => format!("{text:>width$}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Value {
+        expr: Expr,
+        spec: Spec,
+    },
+    Cond {
+        field: Field,
+        negate: bool,
+        then: Vec<Segment>,
+        otherwise: Vec<Segment>,
+    },
+}
+
+/// A parsed output format.
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(fmt: &str) -> Result<Self, FormatError> {
+        let mut parser = Parser {
+            chars: fmt.chars().collect(),
+            pos: 0,
+        };
+        let segments = parser.segments(false)?;
+        Ok(Self { segments })
+    }
+
+    pub fn render(&self, source: &impl FieldSource) -> String {
+        let mut out = String::new();
+        render_segments(&self.segments, source, &mut out);
+        out
+    }
+}
+
+fn render_segments(segments: &[Segment], source: &impl FieldSource, out: &mut String) {
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Value { expr, spec } => out.push_str(&spec.apply(&expr.eval(source))),
+            Segment::Cond {
+                field,
+                negate,
+                then,
+                otherwise,
+            } => {
+                if source.value(*field).is_truthy() != *negate {
+                    render_segments(then, source, out);
+                } else {
+                    render_segments(otherwise, source, out);
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// Parse text and placeholders. When `nested`, stop before an unescaped `|` or `}`.
+    fn segments(&mut self, nested: bool) -> Result<Vec<Segment>, FormatError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if nested && (c == '|' || c == '}') {
+                break;
+            }
+            self.pos += 1;
+            match c {
+                '\\' => match self.next().unwrap_or('\\') {
+                    'n' => text.push('\n'),
+                    other => text.push(other),
+                },
+                '%' => {
+                    let segment = match self.next() {
+                        None => return Err(FormatError::DanglingPercent),
+                        Some('%') => {
+                            text.push('%');
+                            continue;
+                        }
+                        Some('{') => self.braced()?,
+                        Some('p') => {
+                            let field = match self.peek() {
+                                Some('x') => Field::PhysX,
+                                Some('y') => Field::PhysY,
+                                Some('w') => Field::PhysWidth,
+                                Some('h') => Field::PhysHeight,
+                                _ => return Err(FormatError::UnknownPlaceholder("p".to_string())),
+                            };
+                            self.pos += 1;
+                            Segment::Value {
+                                expr: Expr::Field(field),
+                                spec: Spec::default(),
+                            }
+                        }
+                        Some(letter) => Segment::Value {
+                            expr: Expr::Field(
+                                Field::from_letter(letter)
+                                    .ok_or(FormatError::UnknownPlaceholder(letter.to_string()))?,
+                            ),
+                            spec: Spec::default(),
+                        },
+                    };
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(segment);
+                }
+                other => text.push(other),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(segments)
+    }
+
+    /// Parse what follows `%{`, up to and including the closing `}`.
+    fn braced(&mut self) -> Result<Segment, FormatError> {
+        if self.peek() == Some('?') {
+            self.pos += 1;
+            let negate = self.peek() == Some('!');
+            if negate {
+                self.pos += 1;
+            }
+            let name = self.until(&[':', '}'])?;
+            let field = Field::from_name(name.trim())
+                .ok_or_else(|| FormatError::UnknownField(name.trim().to_string()))?;
+            let mut then = Vec::new();
+            let mut otherwise = Vec::new();
+            if self.next() == Some(':') {
+                then = self.segments(true)?;
+                match self.next() {
+                    Some('}') => {}
+                    Some('|') => {
+                        otherwise = self.segments(true)?;
+                        if self.next() != Some('}') {
+                            return Err(FormatError::Unterminated);
+                        }
+                    }
+                    _ => return Err(FormatError::Unterminated),
+                }
+            }
+            return Ok(Segment::Cond {
+                field,
+                negate,
+                then,
+                otherwise,
+            });
+        }
+
+        let expr = self.until(&[':', '}'])?;
+        let spec = match self.next() {
+            Some(':') => {
+                let spec = self.until(&['}'])?;
+                self.pos += 1;
+                Spec::parse(&spec)?
+            }
+            _ => Spec::default(),
+        };
+        Ok(Segment::Value {
+            expr: parse_expr(&expr)?,
+            spec,
+        })
+    }
+
+    /// Take the text up to one of `ends`, which is left to be consumed.
+    fn until(&mut self, ends: &[char]) -> Result<String, FormatError> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None => return Err(FormatError::Unterminated),
+                Some(c) if ends.contains(&c) => break,
+                Some(_) => self.pos += 1,
+            }
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+}
+
+/// Parse `+ - * /` arithmetic on numbers and numeric fields.
+fn parse_expr(source: &str) -> Result<Expr, FormatError> {
+    struct ExprParser<'a> {
+        source: &'a str,
+        chars: Vec<char>,
+        pos: usize,
+    }
+
+    impl ExprParser<'_> {
+        fn invalid(&self) -> FormatError {
+            FormatError::InvalidExpression(self.source.trim().to_string())
+        }
+
+        fn peek(&mut self) -> Option<char> {
+            while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+                self.pos += 1;
+            }
+            self.chars.get(self.pos).copied()
+        }
+
+        fn sum(&mut self) -> Result<Expr, FormatError> {
+            let mut lhs = self.product()?;
+            while let Some(op @ ('+' | '-')) = self.peek() {
+                self.pos += 1;
+                let op = if op == '+' { Op::Add } else { Op::Sub };
+                lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+            }
+            Ok(lhs)
+        }
+
+        fn product(&mut self) -> Result<Expr, FormatError> {
+            let mut lhs = self.factor()?;
+            while let Some(op @ ('*' | '/')) = self.peek() {
+                self.pos += 1;
+                let op = if op == '*' { Op::Mul } else { Op::Div };
+                lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.factor()?));
+            }
+            Ok(lhs)
+        }
+
+        fn factor(&mut self) -> Result<Expr, FormatError> {
+            match self.peek() {
+                Some('-') => {
+                    self.pos += 1;
+                    Ok(Expr::Neg(Box::new(self.factor()?)))
+                }
+                Some('(') => {
+                    self.pos += 1;
+                    let expr = self.sum()?;
+                    if self.peek() != Some(')') {
+                        return Err(self.invalid());
+                    }
+                    self.pos += 1;
+                    Ok(expr)
+                }
+                Some(c) if c.is_ascii_digit() || c == '.' => {
+                    let start = self.pos;
+                    while self
+                        .chars
+                        .get(self.pos)
+                        .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                    {
+                        self.pos += 1;
+                    }
+                    let number: String = self.chars[start..self.pos].iter().collect();
+                    if let Ok(i) = number.parse::<i64>() {
+                        Ok(Expr::Number(Value::Int(i)))
+                    } else {
+                        let f = number.parse::<f64>().map_err(|_| self.invalid())?;
+                        Ok(Expr::Number(Value::Float(f)))
+                    }
+                }
+                Some(c) if c.is_alphabetic() || c == '_' => {
+                    let start = self.pos;
+                    while self
+                        .chars
+                        .get(self.pos)
+                        .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+                    {
+                        self.pos += 1;
+                    }
+                    let name: String = self.chars[start..self.pos].iter().collect();
+                    let field = Field::from_name(&name)
+                        .ok_or_else(|| FormatError::UnknownField(name.clone()))?;
+                    Ok(Expr::Field(field))
+                }
+                _ => Err(self.invalid()),
+            }
+        }
+    }
+
+    let mut parser = ExprParser {
+        source,
+        chars: source.chars().collect(),
+        pos: 0,
+    };
+    let expr = parser.sum()?;
+    if parser.peek().is_some() {
+        return Err(parser.invalid());
+    }
+    // a text field can only be printed as it is
+    match &expr {
+        Expr::Field(_) => {}
+        expr => check_numeric(expr)?,
+    }
+    Ok(expr)
+}
+
+fn check_numeric(expr: &Expr) -> Result<(), FormatError> {
+    match expr {
+        Expr::Number(_) => Ok(()),
+        Expr::Field(field) if field.is_text() => {
+            let (_, name, ..) = Field::ALL
+                .iter()
+                .find(|(f, ..)| f == field)
+                .expect("every field is in the table");
+            Err(FormatError::TextInArithmetic(name.to_string()))
+        }
+        Expr::Field(_) => Ok(()),
+        Expr::Neg(expr) => check_numeric(expr),
+        Expr::Binary(_, lhs, rhs) => {
+            check_numeric(lhs)?;
+            check_numeric(rhs)
+        }
+    }
+}
+
+/// Position and size of the selection in the coordinate spaces exposed by the output formats.
+pub struct Geometry {
+    /// Global logical coordinates.
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Logical coordinates relative to the selected output.
+    pub rel_x: i32,
+    pub rel_y: i32,
+    pub rel_width: i32,
+    pub rel_height: i32,
+    /// Buffer pixels of the selected output.
+    pub phys_x: i32,
+    pub phys_y: i32,
+    pub phys_width: i32,
+    pub phys_height: i32,
+}
+
+impl Geometry {
+    /// With `screen`, the geometry is the one of the whole selected output.
+    pub fn new(info: &AreaInfo, screen: bool) -> Self {
+        let screen_info = info.selected_screen_info();
+        let Position { x: sx, y: sy } = screen_info.get_position();
+        let Size {
+            width: sw,
+            height: sh,
+        } = screen_info.get_size();
+        let Size {
+            width: wl_w,
+            height: wl_h,
+        } = screen_info.get_wloutput_size();
+
+        let (x, y, width, height) = if !screen {
+            let Position { x, y } = info.left_top_point();
+            let w = info.width().max(1);
+            let h = info.height().max(1);
+            (x, y, w, h)
+        } else {
+            let w = sw.max(1);
+            let h = sh.max(1);
+            (sx, sy, w, h)
+        };
+
+        let (phys_x, phys_y, phys_width, phys_height) = if !screen {
+            let Position { x, y } = info.physical_left_top_point();
+            let Size { width, height } = info.physical_size();
+            (x, y, width, height)
+        } else {
+            (0, 0, wl_w, wl_h)
+        };
+
+        let rel_x = x.saturating_sub(sx);
+        let rel_y = y.saturating_sub(sy);
+        let rel_width = width.min(sw.saturating_sub(rel_x));
+        let rel_height = height.min(sh.saturating_sub(rel_y));
+
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rel_x,
+            rel_y,
+            rel_width,
+            rel_height,
+            phys_x,
+            phys_y,
+            phys_width,
+            phys_height,
+        }
+    }
+}
+
+/// Transform names as used by sway and kanshi
+pub fn transform_name(transform: Transform) -> &'static str {
+    match transform {
+        Transform::Normal => "normal",
+        Transform::_90 => "90",
+        Transform::_180 => "180",
+        Transform::_270 => "270",
+        Transform::Flipped => "flipped",
+        Transform::Flipped90 => "flipped-90",
+        Transform::Flipped180 => "flipped-180",
+        Transform::Flipped270 => "flipped-270",
+        _ => "unknown",
+    }
+}
+
+/// The fields of a selection. With `screen`, the selection is the whole output.
+pub struct AreaFields<'a> {
+    info: &'a AreaInfo,
+    geometry: Geometry,
+    screen: bool,
+}
+
+impl<'a> AreaFields<'a> {
+    pub fn new(info: &'a AreaInfo, screen: bool) -> Self {
+        Self {
+            info,
+            geometry: Geometry::new(info, screen),
+            screen,
+        }
+    }
+}
+
+impl FieldSource for AreaFields<'_> {
+    fn value(&self, field: Field) -> Value {
+        let screen_info = self.info.selected_screen_info();
+        let geometry = &self.geometry;
+        let Position { x: sx, y: sy } = screen_info.get_position();
+        let Size {
+            width: sw,
+            height: sh,
+        } = screen_info.get_size();
+        let Size {
+            width: mode_w,
+            height: mode_h,
+        } = screen_info.get_wloutput_size();
+        let Size {
+            width: mm_w,
+            height: mm_h,
+        } = screen_info.get_physical_size();
+        let normalised = |value: i32, size: i32| Value::Float(value as f64 / size.max(1) as f64);
+        let int = |value: i32| Value::Int(value as i64);
+        match field {
+            Field::X => int(geometry.x),
+            Field::Y => int(geometry.y),
+            Field::Width => int(geometry.width),
+            Field::Height => int(geometry.height),
+            Field::RelX => int(geometry.rel_x),
+            Field::RelY => int(geometry.rel_y),
+            Field::RelWidth => int(geometry.rel_width),
+            Field::RelHeight => int(geometry.rel_height),
+            Field::PhysX => int(geometry.phys_x),
+            Field::PhysY => int(geometry.phys_y),
+            Field::PhysWidth => int(geometry.phys_width),
+            Field::PhysHeight => int(geometry.phys_height),
+            Field::NormX => normalised(geometry.rel_x, sw),
+            Field::NormY => normalised(geometry.rel_y, sh),
+            Field::NormWidth => normalised(geometry.rel_width, sw),
+            Field::NormHeight => normalised(geometry.rel_height, sh),
+            Field::OutputX => int(sx),
+            Field::OutputY => int(sy),
+            Field::OutputWidth => int(sw),
+            Field::OutputHeight => int(sh),
+            Field::ModeWidth => int(mode_w),
+            Field::ModeHeight => int(mode_h),
+            Field::MmWidth => int(mm_w),
+            Field::MmHeight => int(mm_h),
+            Field::Scale => Value::Float(screen_info.get_scale()),
+            Field::Refresh => Value::Float(screen_info.get_refresh_rate()),
+            Field::Screen => Value::Int(self.screen as i64),
+            Field::Area => Value::Int(!self.screen as i64),
+            Field::Output => Value::Text(screen_info.get_name().to_string()),
+            Field::Description => Value::Text(screen_info.get_description().to_string()),
+            Field::Make => Value::Text(screen_info.get_make().to_string()),
+            Field::Model => Value::Text(screen_info.get_model().to_string()),
+            Field::Transform => {
+                Value::Text(transform_name(screen_info.get_transform()).to_string())
+            }
+            Field::PhysicalSize => Value::Text(format!("{mm_w}x{mm_h}")),
+            Field::Label => Value::Text(self.info.box_info.label.clone().unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_client::protocol::wl_output::Transform;
+
+    use super::*;
+    use crate::state::tests::{area, screen, size};
+
+    /// the `apply_format` of the CLI before templates, as the reference of the single letters
+    fn baseline(info: &AreaInfo, fmt: &str, screen: bool) -> String {
+        let screen_info = info.selected_screen_info();
+        let Position { x: sx, y: sy } = screen_info.get_position();
+        let Size {
+            width: sw,
+            height: sh,
+        } = screen_info.get_size();
+        let Size {
+            width: wl_w,
+            height: wl_h,
+        } = screen_info.get_wloutput_size();
+
+        let (x, y, width, height) = if !screen {
+            let Position { x, y } = info.left_top_point();
+            (x, y, info.width().max(1), info.height().max(1))
+        } else {
+            (sx, sy, sw.max(1), sh.max(1))
+        };
+        let rel_x = x.saturating_sub(sx);
+        let rel_y = y.saturating_sub(sy);
+        let rel_width = width.min(sw.saturating_sub(rel_x));
+        let rel_height = height.min(sh.saturating_sub(rel_y));
+
+        let mut out = String::new();
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                match chars.next().unwrap_or('%') {
+                    '%' => out.push('%'),
+                    'x' => out.push_str(&x.to_string()),
+                    'y' => out.push_str(&y.to_string()),
+                    'w' => out.push_str(&width.to_string()),
+                    'h' => out.push_str(&height.to_string()),
+                    'X' => out.push_str(&rel_x.to_string()),
+                    'Y' => out.push_str(&rel_y.to_string()),
+                    'W' => out.push_str(&rel_width.to_string()),
+                    'H' => out.push_str(&rel_height.to_string()),
+                    'o' | 'l' => out.push_str(screen_info.get_name()),
+                    'd' => out.push_str(screen_info.get_description()),
+                    'L' => out.push_str(&wl_w.to_string()),
+                    'T' => out.push_str(&wl_h.to_string()),
+                    other => out.push(other),
+                }
+            } else if c == '\\' {
+                match chars.next().unwrap_or('\\') {
+                    'n' => out.push('\n'),
+                    other => out.push(other),
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    fn render(fmt: &str, info: &AreaInfo, screen: bool) -> String {
+        Template::parse(fmt)
+            .unwrap()
+            .render(&AreaFields::new(info, screen))
+    }
+
+    fn sample_area(rect: [f64; 4]) -> AreaInfo {
+        let mut screen_info = screen(
+            Position { x: 1920, y: 0 },
+            size(3840, 2160),
+            size(2560, 1440),
+            Transform::Normal,
+        );
+        screen_info.description = "Dell U2720Q".to_string();
+        area(screen_info, rect)
+    }
+
+    #[test]
+    fn single_letters_render_as_before() {
+        let formats = [
+            "%x,%y %wx%h",
+            "%X %Y %W %H",
+            "%o %l %d",
+            "%L %T",
+            "%x %y %w %h %X %Y %W %H %o %l %d %L %T",
+            "100%% %w\\n",
+            "a\\\\b \\%x\\n\\n",
+            "%o: %wx%h at %x,%y",
+            "",
+            "plain text",
+        ];
+        let areas = [
+            [2000.0, 100.0, 2400.5, 400.7],
+            // dragged up and left
+            [2400.0, 400.0, 2000.0, 100.0],
+            // crossing the right edge of the output
+            [4000.0, 1000.0, 5000.0, 2000.0],
+            // a click
+            [2100.0, 200.0, 2100.0, 200.0],
+        ];
+        for rect in areas {
+            let info = sample_area(rect);
+            for fmt in formats {
+                for screen in [false, true] {
+                    assert_eq!(
+                        render(fmt, &info, screen),
+                        baseline(&info, fmt, screen),
+                        "{fmt:?} on {rect:?}, screen {screen}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn named_fields_specs_arithmetic_and_conditions() {
+        let info = sample_area([2000.0, 100.0, 2400.0, 400.0]);
+        let cases = [
+            ("%{x},%{rel_x} %{width}", "2000,80 400"),
+            ("%{w}x%{h}", "400x300"),
+            ("%px,%py %pwx%ph", "120,150 600x450"),
+            (
+                "[%{x:6}] [%{o:<6}] [%{o:>6}] [%{o:^6}]",
+                "[  2000] [DP-1  ] [  DP-1] [ DP-1 ]",
+            ),
+            ("%{x:06} %{nx:.3} %{scale:.2}", "002000 0.031 1.00"),
+            ("%{w/2} %{(w+h)*2} %{-x} %{w/3:.1}", "200 1400 -2000 133.3"),
+            ("%{?screen:output %o|area %wx%h}", "area 400x300"),
+            ("%{?!label:no label} %{?label:%{label}}", "no label "),
+            ("%{?area:\\|\\}}", "|}"),
+        ];
+        for (fmt, expected) in cases {
+            assert_eq!(render(fmt, &info, false), expected, "{fmt:?}");
+        }
+        assert_eq!(
+            render("%{?screen:output %o|area}", &info, true),
+            "output DP-1"
+        );
+        let mut info = info;
+        info.box_info.label = Some("kitty".to_string());
+        assert_eq!(
+            render("%b %{b} %{label:>6}", &info, false),
+            "kitty kitty  kitty"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |fmt: &str| Template::parse(fmt).unwrap_err();
+        assert!(matches!(error("%{x"), FormatError::Unterminated));
+        assert!(matches!(error("%{?x:a"), FormatError::Unterminated));
+        assert!(matches!(error("%x %"), FormatError::DanglingPercent));
+        assert!(matches!(error("%{o+1}"), FormatError::TextInArithmetic(name) if name == "output"));
+        assert!(matches!(error("%{x:abc}"), FormatError::InvalidSpec(spec) if spec == "abc"));
+        assert!(matches!(error("%{x:.}"), FormatError::InvalidSpec(_)));
+        assert!(matches!(error("%{nope}"), FormatError::UnknownField(name) if name == "nope"));
+        assert!(matches!(error("%{?nope:a}"), FormatError::UnknownField(_)));
+        assert!(matches!(error("%q"), FormatError::UnknownPlaceholder(letter) if letter == "q"));
+        assert!(matches!(error("%pz"), FormatError::UnknownPlaceholder(letter) if letter == "p"));
+        assert!(matches!(error("%{x+}"), FormatError::InvalidExpression(_)));
+        assert!(matches!(error("%{(x}"), FormatError::InvalidExpression(_)));
+    }
+}
@@ -1,17 +1,32 @@
+mod boxes;
 mod dispatch;
+mod preview;
 mod render;
 
 pub mod error;
+pub mod format;
 pub mod state;
+mod stream;
 mod utils;
 pub use utils::*;
 
+use boxes::BoxSet;
 use error::WaySipError;
 use render::UiInit;
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
 pub use state::{AreaInfo, BoxInfo, SelectionType};
-use std::os::unix::prelude::AsFd;
+use std::{
+    collections::HashMap,
+    io::Write,
+    os::{fd::OwnedFd, unix::prelude::AsFd},
+    time::{Duration, Instant},
+};
+pub use stream::BoxSnapshotParser;
+use stream::BoxStream;
+pub use wayland_client::protocol::wl_output::{Subpixel, Transform};
 use wayland_client::{
-    Connection,
+    Connection, DispatchError, EventQueue,
+    backend::WaylandError,
     globals::registry_queue_init,
     protocol::{
         wl_compositor::WlCompositor,
@@ -24,9 +39,12 @@ use wayland_protocols::{
     wp::cursor_shape::v1::client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
     xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1,
 };
-use wayland_protocols_wlr::layer_shell::v1::client::{
-    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
-    zwlr_layer_surface_v1::{self, Anchor},
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, Anchor},
+    },
+    output_management::v1::client::zwlr_output_manager_v1::ZwlrOutputManagerV1,
 };
 
 fn get_cursor_buffer(connection: &Connection, shm: &WlShm) -> Option<CursorImageBuffer> {
@@ -43,8 +61,16 @@ pub struct WaySip {
     conn: Option<Connection>,
     selection_type: SelectionType,
     style: Style,
+    output_styles: HashMap<String, Style>,
     predefined_boxes: Option<Vec<state::BoxInfo>>,
+    box_policy: BoxPolicy,
+    box_stream: Option<BoxStream>,
     aspect_ratio: Option<(f64, f64)>,
+    aspect_presets: Vec<Option<(f64, f64)>>,
+    stamp_anchor: StampAnchor,
+    size_limits: SizeLimits,
+    bounds: Option<Bounds>,
+    timeout: Option<Duration>,
 }
 
 impl WaySip {
@@ -93,37 +119,185 @@ impl WaySip {
         self
     }
 
+    /// set the label drawn next to the selection, `None` hides it
+    pub fn with_label(mut self, label: Option<format::Template>) -> Self {
+        self.style.label = label;
+        self
+    }
+    pub fn with_label_corner(mut self, corner: LabelCorner) -> Self {
+        self.style.label_corner = corner;
+        self
+    }
+    pub fn with_label_background_color(mut self, color: Color) -> Self {
+        self.style.label_background_color = color;
+        self
+    }
+
+    /// draw lines through the pointer across all the outputs
+    pub fn with_crosshair(mut self, crosshair: bool) -> Self {
+        self.style.crosshair = crosshair;
+        self
+    }
+    /// draw pixel rulers along the edges of the outputs, with the position of the pointer
+    pub fn with_rulers(mut self, rulers: bool) -> Self {
+        self.style.rulers = rulers;
+        self
+    }
+
+    /// draw composition guides inside the selection
+    pub fn with_composition_guides(mut self, guides: CompositionGuides) -> Self {
+        self.style.composition_guides = guides;
+        self
+    }
+
+    /// set the dash pattern of the border, an empty one draws it solid
+    pub fn with_border_dash(mut self, dash: Vec<f64>) -> Self {
+        self.style.border_dash = dash;
+        self
+    }
+    pub fn with_corner_radius(mut self, radius: f64) -> Self {
+        self.style.corner_radius = radius;
+        self
+    }
+    /// animate the dashes of the border, they stop moving after a few seconds without input
+    pub fn with_marching_ants(mut self, marching_ants: bool) -> Self {
+        self.style.marching_ants = marching_ants;
+        self
+    }
+    pub fn with_border_position(mut self, position: BorderPosition) -> Self {
+        self.style.border_position = position;
+        self
+    }
+
+    /// set the background of the outputs without the selection or the pointer, an opaque color
+    /// blacks them out
+    pub fn with_inactive_color(mut self, color: Color) -> Self {
+        self.style.inactive_color = Some(color);
+        self
+    }
+    /// set the fill of the hovered output when selecting a screen
+    pub fn with_highlight_color(mut self, color: Color) -> Self {
+        self.style.highlight_color = Some(color);
+        self
+    }
+    /// outline the hovered output when selecting a screen
+    pub fn with_outline_hovered(mut self, outline: bool) -> Self {
+        self.style.outline_hovered = outline;
+        self
+    }
+    /// set the info card of the hovered output when selecting a screen, `None` hides it
+    pub fn with_screen_card(mut self, card: Option<format::Template>) -> Self {
+        self.style.screen_card = card;
+        self
+    }
+    pub fn with_screen_card_position(mut self, position: CardPosition) -> Self {
+        self.style.screen_card_position = position;
+        self
+    }
+    /// show a status line with the mode, the aspect ratio and the main keys on the focused output
+    pub fn with_status_line(mut self, status_line: bool) -> Self {
+        self.style.status_line = status_line;
+        self
+    }
+    /// use another style on the output with this name
+    pub fn with_output_style(mut self, name: impl Into<String>, style: Style) -> Self {
+        self.output_styles.insert(name.into(), style);
+        self
+    }
+    /// get the style of the outputs without their own style
+    pub fn get_style(&self) -> &Style {
+        &self.style
+    }
+
     pub fn with_predefined_boxes(mut self, boxes: Vec<state::BoxInfo>) -> Self {
         self.predefined_boxes = Some(boxes);
         self
     }
 
+    /// choose which of the predefined boxes under the pointer is picked first
+    pub fn with_box_policy(mut self, policy: BoxPolicy) -> Self {
+        self.box_policy = policy;
+        self
+    }
+    /// replace the predefined boxes while the overlay is shown, with the snapshots read from `fd`,
+    /// one per line, parsed by `parse`. Lines longer than 16 MiB are skipped
+    pub fn with_box_stream(mut self, fd: impl Into<OwnedFd>, parse: BoxSnapshotParser) -> Self {
+        self.box_stream = Some(BoxStream::new(fd.into(), parse));
+        self
+    }
+    /// set the fill of the predefined box under the pointer
+    pub fn with_box_hover_color(mut self, color: Color) -> Self {
+        self.style.box_hover_color = Some(color);
+        self
+    }
+
     pub fn with_aspect_ratio(mut self, width: f64, height: f64) -> Self {
         self.aspect_ratio = Some((width, height));
         self
     }
 
+    /// the aspect ratios the wheel and Tab go through while dragging, `None` for a free one. The
+    /// selection starts with the ratio of [Self::with_aspect_ratio], or free without it
+    pub fn with_aspect_presets(mut self, presets: Vec<Option<(f64, f64)>>) -> Self {
+        self.aspect_presets = presets;
+        self
+    }
+
+    /// choose the point of a [SelectionType::FixedSize] area held at the pointer
+    pub fn with_stamp_anchor(mut self, anchor: StampAnchor) -> Self {
+        self.stamp_anchor = anchor;
+        self
+    }
+
+    /// keep the selection between a minimum and a maximum size, it is clamped while dragging
+    pub fn with_size_limits(mut self, limits: SizeLimits) -> Self {
+        self.size_limits = limits;
+        self
+    }
+
+    /// keep the selection inside `bounds`, it is clamped while dragging
+    pub fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// set the border of the selection while it is clamped
+    pub fn with_clamp_color(mut self, color: Color) -> Self {
+        self.style.clamp_color = color;
+        self
+    }
+
+    /// close the overlay after `timeout`, as if the selection was canceled
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Draw the overlay offscreen, without a compositor, and write it to `out` as a PNG: two
+    /// outputs of `size` side by side, an area selection with its label and the predefined boxes
+    /// on the first one, the hovered screen with its info card on the second one.
+    pub fn preview(&self, size: Size, out: &mut impl Write) -> Result<(), WaySipError> {
+        let image = preview::draw_preview(
+            &self.style,
+            self.predefined_boxes.as_ref(),
+            self.aspect_ratio,
+            size,
+        )
+        .map_err(|e| WaySipError::PreviewFailed(e.to_string()))?;
+        image
+            .write_to_png(out)
+            .map_err(|e| WaySipError::PreviewFailed(e.to_string()))
+    }
+
     /// get the selected area
-    pub fn get(self) -> Result<Option<state::AreaInfo>, WaySipError> {
-        match self.conn {
-            Some(connection) => get_area_inner(
-                &connection,
-                self.selection_type,
-                self.style,
-                self.predefined_boxes,
-                self.aspect_ratio,
-            ),
+    pub fn get(mut self) -> Result<Option<state::AreaInfo>, WaySipError> {
+        match self.conn.take() {
+            Some(connection) => get_area_inner(&connection, self),
             None => {
                 let connection = Connection::connect_to_env()
                     .map_err(|e| WaySipError::InitFailed(e.to_string()))?;
 
-                get_area_inner(
-                    &connection,
-                    self.selection_type,
-                    self.style,
-                    self.predefined_boxes,
-                    self.aspect_ratio,
-                )
+                get_area_inner(&connection, self)
             }
         }
     }
@@ -131,17 +305,42 @@ impl WaySip {
 
 fn get_area_inner(
     connection: &Connection,
-    selection_type: SelectionType,
-    style: Style,
-    boxes: Option<Vec<state::BoxInfo>>,
-    aspect_ratio: Option<(f64, f64)>,
+    options: WaySip,
 ) -> Result<Option<state::AreaInfo>, WaySipError> {
+    let WaySip {
+        selection_type,
+        style,
+        output_styles,
+        predefined_boxes,
+        box_policy,
+        mut box_stream,
+        aspect_ratio,
+        aspect_presets,
+        stamp_anchor,
+        size_limits,
+        bounds,
+        timeout,
+        ..
+    } = options;
     let (globals, _) = registry_queue_init::<state::WaysipState>(connection)
         .map_err(|e| WaySipError::InitFailed(e.to_string()))?;
     let mut state = state::WaysipState::new(selection_type);
 
-    state.predefined_boxes = boxes;
+    // the overlay shows up before the first snapshot of the stream
+    state.predefined_boxes = match box_stream {
+        Some(_) => Some(predefined_boxes.unwrap_or_default()),
+        None => predefined_boxes,
+    }
+    .map(BoxSet::new);
+    state.box_policy = box_policy;
     state.aspect_ratio = aspect_ratio;
+    state.aspect_preset = aspect_presets
+        .iter()
+        .position(|preset| *preset == aspect_ratio);
+    state.aspect_presets = aspect_presets;
+    state.stamp_anchor = stamp_anchor;
+    state.size_limits = size_limits;
+    state.bounds = bounds;
 
     let mut event_queue = connection.new_event_queue::<state::WaysipState>();
     let qh = event_queue.handle();
@@ -183,6 +382,11 @@ fn get_area_inner(
         .bind::<ZxdgOutputManagerV1, _, _>(&qh, 1..=3, ())
         .map_err(WaySipError::NotSupportedProtocol)?;
 
+    // optional, only used for the exact scale and the enabled state of the outputs
+    let output_manager = globals
+        .bind::<ZwlrOutputManagerV1, _, _>(&qh, 1..=4, ())
+        .ok();
+
     for wloutput in state.wloutput_infos.iter_mut() {
         let zwloutput = xdg_output_manager.get_xdg_output(wloutput.get_output(), &qh, ());
         wloutput
@@ -198,6 +402,16 @@ fn get_area_inner(
     // you will find you get the outputs, but if you do not
     // do the step before, you get empty list
 
+    // the boxes of a stream are not there yet, their label is looked up while dragging
+    let streamed_box = matches!(state.bounds, Some(Bounds::Box(_))) && box_stream.is_some();
+    if state.bounds.is_some() && !streamed_box && state.bounds_rect(state.current_pos).is_none() {
+        return Err(WaySipError::InvalidBounds(match state.bounds.as_ref() {
+            Some(Bounds::NamedOutput(name)) => format!("no output named `{name}`"),
+            Some(Bounds::Box(label)) => format!("no predefined box labelled `{label}`"),
+            _ => "no output".to_string(),
+        }));
+    }
+
     let layer_shell = globals
         .bind::<ZwlrLayerShellV1, _, _>(&qh, 3..=4, ())
         .map_err(WaySipError::NotSupportedProtocol)?;
@@ -210,6 +424,11 @@ fn get_area_inner(
         let wl_surface = wmcompositer.create_surface(&qh, ()); // and create a surface. if two or more,
         // we need to create more
         let zwlinfo = wloutput.xdg_output_info();
+        let name = match wloutput.name.is_empty() {
+            true => &zwlinfo.name,
+            false => &wloutput.name,
+        };
+        let style = output_styles.get(name).unwrap_or(&style).clone();
         let Size {
             width: init_w,
             height: init_h,
@@ -250,28 +469,28 @@ fn get_area_inner(
             cursor_surface,
             buffer,
             cursor_buffer: cursor_buffer.clone(),
-            cairo_t,
             inited: false,
             buffer_busy: true,
             stride,
-            style: style.clone(),
-            pango_layout: std::cell::OnceCell::new(),
-            font_desc_bold: std::cell::OnceCell::new(),
-            font_desc_normal: std::cell::OnceCell::new(),
-            prev_selection: None,
-            margin: std::cell::OnceCell::new(),
+            canvas: render::Canvas::new(cairo_t, style),
         });
     }
     state.shm = Some(shm);
 
     state.qh = Some(qh);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut timed_out = false;
     while state.running {
-        event_queue
-            .blocking_dispatch(&mut state)
-            .map_err(WaySipError::DispatchError)?;
+        timed_out = !dispatch_until(&mut event_queue, &mut state, deadline, &mut box_stream)?;
+        if timed_out {
+            state.running = false;
+        }
     }
 
     layer_shell.destroy();
+    if let Some(output_manager) = output_manager {
+        output_manager.stop();
+    }
     for surface in &state.wl_surfaces {
         surface.layer.destroy();
         surface.wl_surface.destroy();
@@ -279,5 +498,118 @@ fn get_area_inner(
         surface.buffer.destroy();
     }
     state.wl_surfaces.clear();
+    if timed_out || state.is_identify() {
+        return Ok(None);
+    }
     Ok(state.area_info())
 }
+
+/// Dispatch the events, waiting for them until `deadline` at most, and apply the snapshots of the
+/// box stream. Returns false when the deadline passed.
+fn dispatch_until(
+    event_queue: &mut EventQueue<state::WaysipState>,
+    state: &mut state::WaysipState,
+    deadline: Option<Instant>,
+    box_stream: &mut Option<BoxStream>,
+) -> Result<bool, WaySipError> {
+    if deadline.is_none() && box_stream.is_none() {
+        event_queue
+            .blocking_dispatch(state)
+            .map_err(WaySipError::DispatchError)?;
+        return Ok(true);
+    }
+    let backend_error = |e| WaySipError::DispatchError(DispatchError::Backend(e));
+    if event_queue
+        .dispatch_pending(state)
+        .map_err(WaySipError::DispatchError)?
+        > 0
+    {
+        return Ok(true);
+    }
+    event_queue.flush().map_err(backend_error)?;
+    // events may have been read by another queue in the meantime
+    let Some(guard) = event_queue.prepare_read() else {
+        event_queue
+            .dispatch_pending(state)
+            .map_err(WaySipError::DispatchError)?;
+        return Ok(true);
+    };
+    let fd = guard.connection_fd();
+    let mut fds = vec![PollFd::new(&fd, PollFlags::IN)];
+    if let Some(stream) = box_stream.as_ref() {
+        fds.push(PollFd::new(&stream.file, PollFlags::IN));
+    }
+    let ready =
+        poll_until(&mut fds, deadline).map_err(|e| backend_error(WaylandError::Io(e.into())))?;
+    if ready == 0 {
+        return Ok(false);
+    }
+    let wayland_ready = !fds[0].revents().is_empty();
+    let stream_ready = fds.get(1).is_some_and(|fd| !fd.revents().is_empty());
+    drop(fds);
+    if wayland_ready {
+        guard.read().map_err(backend_error)?;
+    } else {
+        drop(guard);
+    }
+    if stream_ready {
+        let stream = box_stream.as_mut().expect("polled with the stream");
+        if let Some(boxes) = stream.read() {
+            state.set_boxes(boxes);
+        }
+        if stream.closed {
+            *box_stream = None;
+        }
+    }
+    event_queue
+        .dispatch_pending(state)
+        .map_err(WaySipError::DispatchError)?;
+    Ok(true)
+}
+
+/// Poll `fds` until `deadline` at most, also after a signal. Returns the number of ready fds, 0
+/// when the deadline passed.
+fn poll_until(fds: &mut [PollFd], deadline: Option<Instant>) -> rustix::io::Result<usize> {
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                    return Ok(0);
+                };
+                Some(Timespec::try_from(timeout).unwrap_or(Timespec {
+                    tv_sec: i64::MAX,
+                    tv_nsec: 0,
+                }))
+            }
+            None => None,
+        };
+        match poll(fds, timeout.as_ref()) {
+            Err(rustix::io::Errno::INTR) => continue,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, os::unix::net::UnixStream, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn polls_until_the_deadline() {
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        let mut fds = [PollFd::new(&reader, PollFlags::IN)];
+        let start = Instant::now();
+        assert_eq!(poll_until(&mut fds, Some(start)), Ok(0));
+
+        let wait = Duration::from_millis(50);
+        assert_eq!(poll_until(&mut fds, Some(start + wait)), Ok(0));
+        assert!(start.elapsed() >= wait);
+
+        writer.write_all(b"\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert_eq!(poll_until(&mut fds, Some(deadline)), Ok(1));
+        assert_eq!(poll_until(&mut fds, None), Ok(1));
+    }
+}
@@ -0,0 +1,214 @@
+//! Offscreen drawing of the overlay, with a synthetic selection, so that a style can be looked at
+//! without a compositor.
+
+use cairo::{Context, Format, ImageSurface};
+
+use crate::{
+    BoxInfo, Position, SelectionType, Size, Style,
+    boxes::BoxSet,
+    format::{Field, FieldSource, Value},
+    render::{Canvas, Hud},
+    state::status_text,
+};
+
+/// names of the two synthetic outputs, side by side
+const PREVIEW_OUTPUTS: [&str; 2] = ["PREVIEW-1", "PREVIEW-2"];
+
+/// Draw two outputs of `size` side by side: an area selection with its label, the boxes and the
+/// status line on the first one, the hovered screen with its info card on the second one. Without
+/// boxes, two synthetic ones are drawn.
+pub(crate) fn draw_preview(
+    style: &Style,
+    boxes: Option<&Vec<BoxInfo>>,
+    aspect_ratio: Option<(f64, f64)>,
+    size: Size,
+) -> Result<ImageSurface, cairo::Error> {
+    let Size { width, height } = size;
+    let (w, h) = (width as f64, height as f64);
+
+    let area = ImageSurface::create(Format::ARgb32, width, height)?;
+    let mut canvas = Canvas::new(Context::new(&area)?, style.clone());
+    canvas.hud = Hud {
+        status: style
+            .status_line
+            .then(|| status_text(SelectionType::Area, aspect_ratio)),
+        help: None,
+    };
+    let start_pos = Position {
+        x: (w * 0.3).round(),
+        y: (h * 0.3).round(),
+    };
+    let end_pos = Position {
+        x: (w * 0.65).round(),
+        y: (h * 0.7).round(),
+    };
+    let label = style.label.as_ref().map(|template| {
+        template.render(&PreviewFields {
+            output: 0,
+            size,
+            rect: [start_pos.x, start_pos.y, end_pos.x, end_pos.y],
+            screen: false,
+        })
+    });
+    let synthetic_boxes = vec![
+        BoxInfo {
+            start_x: (w * 0.05).round(),
+            start_y: (h * 0.1).round(),
+            end_x: (w * 0.2).round(),
+            end_y: (h * 0.35).round(),
+            label: Some("terminal".to_string()),
+            color: None,
+            z: 0,
+        },
+        BoxInfo {
+            start_x: (w * 0.75).round(),
+            start_y: (h * 0.55).round(),
+            end_x: (w * 0.95).round(),
+            end_y: (h * 0.9).round(),
+            label: Some("browser".to_string()),
+            color: None,
+            z: 0,
+        },
+    ];
+    canvas.draw_selection(
+        start_pos,
+        end_pos,
+        Position { x: 0, y: 0 },
+        size,
+        end_pos,
+        label.as_deref(),
+        Some(&BoxSet::new(boxes.cloned().unwrap_or(synthetic_boxes))),
+        0.0,
+        true,
+    );
+    drop(canvas);
+
+    let screen = ImageSurface::create(Format::ARgb32, width, height)?;
+    let mut canvas = Canvas::new(Context::new(&screen)?, style.clone());
+    let card = style.screen_card.as_ref().map(|template| {
+        template.render(&PreviewFields {
+            output: 1,
+            size,
+            rect: [w, 0.0, 2.0 * w, h],
+            screen: true,
+        })
+    });
+    canvas.draw_select_screen(
+        true,
+        size,
+        Position { x: width, y: 0 },
+        card.as_deref(),
+        Position {
+            x: w * 1.5,
+            y: h * 0.5,
+        },
+    );
+    drop(canvas);
+
+    let preview = ImageSurface::create(Format::ARgb32, 2 * width, height)?;
+    let cairoinfo = Context::new(&preview)?;
+    for (x, output) in [(0.0, &area), (w, &screen)] {
+        cairoinfo.set_source_surface(output, x, 0.0)?;
+        cairoinfo.paint()?;
+    }
+    drop(cairoinfo);
+    Ok(preview)
+}
+
+/// The fields of a selection on a synthetic output, at scale 1 and 96 dpi
+struct PreviewFields {
+    /// index of the output in [PREVIEW_OUTPUTS]
+    output: usize,
+    size: Size,
+    /// `[x1, y1, x2, y2]`, in global logical coordinates
+    rect: [f64; 4],
+    /// whether the selection is the whole output
+    screen: bool,
+}
+
+impl FieldSource for PreviewFields {
+    fn value(&self, field: Field) -> Value {
+        let Size { width, height } = self.size;
+        let output_x = self.output as i32 * width;
+        let [x1, y1, x2, y2] = self.rect.map(|v| v as i32);
+        let (sel_w, sel_h) = ((x2 - x1).max(1), (y2 - y1).max(1));
+        let (rel_x, rel_y) = (x1 - output_x, y1);
+        let mm = |pixels: i32| pixels * 254 / 960;
+        let normalised = |value: i32, size: i32| Value::Float(value as f64 / size.max(1) as f64);
+        let int = |value: i32| Value::Int(value as i64);
+        match field {
+            Field::X => int(x1),
+            Field::Y => int(y1),
+            Field::Width => int(sel_w),
+            Field::Height => int(sel_h),
+            Field::RelX | Field::PhysX => int(rel_x),
+            Field::RelY | Field::PhysY => int(rel_y),
+            Field::RelWidth | Field::PhysWidth => int(sel_w),
+            Field::RelHeight | Field::PhysHeight => int(sel_h),
+            Field::NormX => normalised(rel_x, width),
+            Field::NormY => normalised(rel_y, height),
+            Field::NormWidth => normalised(sel_w, width),
+            Field::NormHeight => normalised(sel_h, height),
+            Field::OutputX => int(output_x),
+            Field::OutputY => int(0),
+            Field::OutputWidth | Field::ModeWidth => int(width),
+            Field::OutputHeight | Field::ModeHeight => int(height),
+            Field::MmWidth => int(mm(width)),
+            Field::MmHeight => int(mm(height)),
+            Field::Scale => Value::Float(1.0),
+            Field::Refresh => Value::Float(60.0),
+            Field::Screen => Value::Int(self.screen as i64),
+            Field::Area => Value::Int(!self.screen as i64),
+            Field::Output => Value::Text(PREVIEW_OUTPUTS[self.output].to_string()),
+            Field::Description => Value::Text(format!("Preview {}", PREVIEW_OUTPUTS[self.output])),
+            Field::Make => Value::Text("waysip".to_string()),
+            Field::Model => Value::Text("preview".to_string()),
+            Field::Transform => Value::Text("normal".to_string()),
+            Field::PhysicalSize => Value::Text(format!("{}x{}", mm(width), mm(height))),
+            Field::Label => Value::Text(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Color;
+
+    use super::*;
+
+    /// the pixel at `x`, `y` as `0xAARRGGBB`, premultiplied
+    fn pixel(surface: &mut ImageSurface, x: usize, y: usize) -> u32 {
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        let offset = y * stride + 4 * x;
+        u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn preview_draws_both_outputs() {
+        let size = Size {
+            width: 200,
+            height: 100,
+        };
+        let preview = draw_preview(&Style::default(), None, None, size).unwrap();
+        assert_eq!((preview.width(), preview.height()), (400, 100));
+
+        let opaque = |r, g, b| Color { r, g, b, a: 1.0 };
+        let style = Style {
+            background_color: opaque(1.0, 0.0, 0.0),
+            foreground_color: opaque(0.0, 1.0, 0.0),
+            label: None,
+            screen_card: None,
+            ..Style::default()
+        };
+        let mut preview = draw_preview(&style, Some(&Vec::new()), None, size).unwrap();
+        // the background around the selection, which spans 60,30 to 130,70
+        assert_eq!(pixel(&mut preview, 2, 2), 0xffff0000);
+        assert_eq!(pixel(&mut preview, 197, 97), 0xffff0000);
+        // the selection
+        assert_eq!(pixel(&mut preview, 95, 50), 0xff00ff00);
+        // the hovered screen, filled all over
+        assert_eq!(pixel(&mut preview, 202, 2), 0xff00ff00);
+        assert_eq!(pixel(&mut preview, 300, 50), 0xff00ff00);
+    }
+}
@@ -1,8 +1,12 @@
-use super::state::LayerSurfaceInfo;
-use crate::{BoxInfo, Size, utils::Position};
+use super::state::{LayerSurfaceInfo, ScreenInfo};
+use crate::{
+    BorderPosition, BoxInfo, CardPosition, Color, CompositionGuides, LabelCorner, Size, Style,
+    boxes::BoxSet, format::transform_name, utils::Position,
+};
 use cairo::{Context, Format};
 use memmap2::MmapMut;
-use std::fs::File;
+use std::{cell::OnceCell, fs::File};
+use wayland_client::protocol::wl_output::Transform;
 
 impl LayerSurfaceInfo {
     pub fn init_commit(&self) {
@@ -11,98 +15,203 @@ impl LayerSurfaceInfo {
     }
 
     pub fn redraw_select_screen(
-        &self,
+        &mut self,
+        is_selected: bool,
+        size: Size,
+        start_position: Position,
+        card: Option<&str>,
+        pointer: Position<f64>,
+    ) {
+        self.canvas
+            .draw_select_screen(is_selected, size, start_position, card, pointer);
+        self.commit_damage(&Damage::All, size);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn redraw(
+        &mut self,
+        start_pos: Position<f64>,
+        end_pos: Position<f64>,
+        start_position: Position,
+        size: Size,
+        pointer: Position<f64>,
+        label: Option<&str>,
+        opt_boxes: Option<&BoxSet>,
+        dash_offset: f64,
+        redraw_all: bool,
+    ) {
+        let damage = self.canvas.draw_selection(
+            start_pos,
+            end_pos,
+            start_position,
+            size,
+            pointer,
+            label,
+            opt_boxes,
+            dash_offset,
+            redraw_all,
+        );
+        self.commit_damage(&damage, size);
+    }
+
+    pub fn redraw_pointer(&mut self, pointer: Position<f64>, start_position: Position, size: Size) {
+        if let Some(damage) = self.canvas.draw_pointer(pointer, start_position, size) {
+            self.commit_damage(&damage, size);
+        }
+    }
+
+    pub fn redraw_identify(&self, index: usize, info: &ScreenInfo) {
+        self.canvas.draw_identify(index, info);
+        self.commit_damage(&Damage::All, info.get_size());
+    }
+
+    fn commit_damage(&self, damage: &Damage, Size { width, height }: Size) {
+        self.wl_surface.attach(Some(&self.buffer), 0, 0);
+        match damage {
+            Damage::All => self.wl_surface.damage(0, 0, width, height),
+            Damage::Rects(rects) => {
+                for [x, y, w, h] in rects {
+                    self.wl_surface.damage(*x, *y, *w, *h);
+                }
+            }
+        }
+        self.wl_surface.commit();
+    }
+}
+
+/// What a drawing changed on the surface
+pub(crate) enum Damage {
+    All,
+    Rects(Vec<[i32; 4]>),
+}
+
+/// The drawing state of a surface, apart from its wayland objects, so that the overlay can also be
+/// drawn offscreen.
+#[derive(Debug)]
+pub(crate) struct Canvas {
+    pub cairo_t: Context,
+    pub style: Style,
+    pub pango_layout: OnceCell<pango::Layout>,
+    pub font_desc_bold: OnceCell<pango::FontDescription>,
+    pub font_desc_normal: OnceCell<pango::FontDescription>,
+    pub prev_selection: Option<[f64; 4]>,
+    /// the rect covered by the label at the previous redraw, damaged by the next one
+    pub prev_label: Option<[f64; 4]>,
+    /// the rects covered by the guides at the previous redraw, `None` until they are first drawn
+    pub prev_guides: Option<Vec<[f64; 4]>>,
+    /// whether the output had the selection or the pointer at the previous redraw
+    pub prev_active: Option<bool>,
+    /// the status line and the help panel to draw
+    pub hud: Hud,
+    /// the status line and the help panel drawn at the previous redraw
+    pub prev_hud: Vec<HudItem>,
+    /// whether the selection is a hovered predefined box
+    pub hovered_box: bool,
+    /// whether the selection is clamped to the size limits or the bounds
+    pub clamped: bool,
+}
+
+/// The texts drawn over the overlay on the focused output
+#[derive(Debug, Default)]
+pub(crate) struct Hud {
+    /// the status line, at the bottom of the output
+    pub status: Option<String>,
+    /// the help panel, at the center of the output
+    pub help: Option<String>,
+}
+
+/// A text of the [Hud], with the rect it covers
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HudItem {
+    text: String,
+    rect: [f64; 4],
+}
+
+impl Canvas {
+    pub fn new(cairo_t: Context, style: Style) -> Self {
+        Self {
+            cairo_t,
+            style,
+            pango_layout: OnceCell::new(),
+            font_desc_bold: OnceCell::new(),
+            font_desc_normal: OnceCell::new(),
+            prev_selection: None,
+            prev_label: None,
+            prev_guides: None,
+            prev_active: None,
+            hud: Hud::default(),
+            prev_hud: Vec::new(),
+            hovered_box: false,
+            clamped: false,
+        }
+    }
+
+    pub fn draw_select_screen(
+        &mut self,
         is_selected: bool,
         Size { width, height }: Size,
         Position {
             x: start_x,
             y: start_y,
         }: Position,
-        name: &str,
-        description: &str,
+        card: Option<&str>,
+        pointer: Position<f64>,
     ) {
         let cairoinfo = &self.cairo_t;
         cairoinfo.set_operator(cairo::Operator::Source);
         if is_selected {
-            cairoinfo.set_source_rgba(
-                self.style.foreground_color.r,
-                self.style.foreground_color.g,
-                self.style.foreground_color.b,
-                self.style.foreground_color.a,
-            );
+            let highlight = self
+                .style
+                .highlight_color
+                .unwrap_or(self.style.foreground_color);
+            cairoinfo.set_source_rgba(highlight.r, highlight.g, highlight.b, highlight.a);
             cairoinfo.paint().unwrap();
 
-            cairoinfo.set_source_rgba(
-                self.style.border_text_color.r,
-                self.style.border_text_color.g,
-                self.style.border_text_color.b,
-                self.style.border_text_color.a,
-            );
-
-            let font_size = self.style.font_size;
-            let pangolayout = self
-                .pango_layout
-                .get_or_init(|| pangocairo::functions::create_layout(cairoinfo));
-            let desc = self.font_desc_normal.get_or_init(|| {
-                let mut d = pango::FontDescription::new();
-                d.set_family(self.style.font_name.as_str());
-                d.set_weight(pango::Weight::Normal);
-                d.set_size(font_size * pango::SCALE);
-                d
-            });
-            pangolayout.set_font_description(Some(desc));
-
-            let name_txt = format!("{name}  {description}");
-            pangolayout.set_text(&name_txt);
-            let (txt1_x, txt1_y) = pangolayout.pixel_size();
-            cairoinfo.save().unwrap();
-            cairoinfo.move_to(10., 60.);
-            pangocairo::functions::show_layout(cairoinfo, pangolayout);
-            cairoinfo.restore().unwrap();
+            if self.style.outline_hovered {
+                let weight = self.style.border_weight;
+                cairoinfo.rectangle(
+                    weight / 2.0,
+                    weight / 2.0,
+                    (width as f64 - weight).max(0.0),
+                    (height as f64 - weight).max(0.0),
+                );
+                cairoinfo.set_source_rgba(
+                    self.style.border_text_color.r,
+                    self.style.border_text_color.g,
+                    self.style.border_text_color.b,
+                    self.style.border_text_color.a,
+                );
+                cairoinfo.set_line_width(weight);
+                cairoinfo.stroke().unwrap();
+            }
 
-            let pos_txt = format!("pos: {start_x}, {start_y}");
-            pangolayout.set_text(&pos_txt);
-            let (txt2_x, txt2_y) = pangolayout.pixel_size();
-            cairoinfo.save().unwrap();
-            cairoinfo.move_to(10., txt1_y as f64 + 65.0);
-            pangocairo::functions::show_layout(cairoinfo, pangolayout);
-            cairoinfo.restore().unwrap();
+            if let Some(card) = card.filter(|card| !card.is_empty()) {
+                self.draw_screen_card(card, Size { width, height });
+            }
+        } else {
+            let background = self.background_color(false);
+            cairoinfo.set_source_rgba(background.r, background.g, background.b, background.a);
+            cairoinfo.paint().unwrap();
+        }
 
+        let size = Size { width, height };
+        let start_position = Position {
+            x: start_x,
+            y: start_y,
+        };
+        if let Some(guides) = self.layout_guides(pointer, start_position, size) {
             cairoinfo.save().unwrap();
-            cairoinfo.set_operator(cairo::Operator::DestOver);
-            cairoinfo.rectangle(
-                5.0,
-                55.0,
-                txt1_x.max(txt2_x) as f64 + 10.0,
-                (txt1_y + txt2_y) as f64 + 16.0,
-            );
-            cairoinfo.set_source_rgba(
-                self.style.background_color.r,
-                self.style.background_color.g,
-                self.style.background_color.b,
-                self.style.background_color.a,
-            );
-            cairoinfo.fill().unwrap();
-
+            self.draw_guides(&guides);
             cairoinfo.restore().unwrap();
-            cairoinfo.set_operator(cairo::Operator::Over);
-        } else {
-            cairoinfo.set_source_rgba(
-                self.style.background_color.r,
-                self.style.background_color.g,
-                self.style.background_color.b,
-                self.style.background_color.a,
-            );
-            cairoinfo.paint().unwrap();
         }
 
-        self.wl_surface.attach(Some(&self.buffer), 0, 0);
-        self.wl_surface.damage(0, 0, width, height);
-        self.wl_surface.commit();
+        let hud = self.layout_hud(size);
+        self.draw_hud(&hud);
+        self.prev_hud = hud;
     }
 
     #[allow(clippy::too_many_arguments)]
-    pub fn redraw(
+    pub fn draw_selection(
         &mut self,
         Position {
             x: start_pos_x,
@@ -113,11 +222,14 @@ impl LayerSurfaceInfo {
             x: start_x,
             y: start_y,
         }: Position,
-        Size { width, height }: Size,
-        draw_text: bool,
-        opt_boxes: Option<&Vec<BoxInfo>>,
+        size: Size,
+        pointer: Position<f64>,
+        label: Option<&str>,
+        opt_boxes: Option<&BoxSet>,
+        dash_offset: f64,
         redraw_all: bool,
-    ) {
+    ) -> Damage {
+        let Size { width, height } = size;
         let cairoinfo = &self.cairo_t;
 
         let current_sel = {
@@ -130,163 +242,898 @@ impl LayerSurfaceInfo {
 
         let border_margin = self.style.border_weight + 2.0;
 
-        let (text_margin_w, text_margin_h) = *self.margin.get_or_init(|| {
-            if !draw_text {
-                return (
-                    self.style.border_weight + 2.0,
-                    self.style.border_weight + 2.0,
-                );
+        // NOTE: the label is laid out first, so the damage covers where it is actually drawn
+        let current_label = match label {
+            Some(text) if !text.is_empty() => {
+                let layout = self.layout();
+                let desc = self.font_desc_bold.get_or_init(|| {
+                    let mut d = pango::FontDescription::new();
+                    d.set_family(self.style.font_name.as_str());
+                    d.set_weight(pango::Weight::Bold);
+                    d.set_size(self.style.font_size * pango::SCALE);
+                    d
+                });
+                layout.set_font_description(Some(desc));
+                layout.set_text(text);
+                let (tw, th) = layout.pixel_size();
+                label_rect(
+                    current_sel,
+                    (
+                        tw as f64 + 2.0 * LABEL_PADDING,
+                        th as f64 + 2.0 * LABEL_PADDING,
+                    ),
+                    self.style.label_corner,
+                    (width as f64, height as f64),
+                )
             }
-            let layout = self
-                .pango_layout
-                .get_or_init(|| pangocairo::functions::create_layout(cairoinfo));
-            let desc = self.font_desc_bold.get_or_init(|| {
-                let mut d = pango::FontDescription::new();
-                d.set_family(self.style.font_name.as_str());
-                d.set_weight(pango::Weight::Bold);
-                d.set_size(self.style.font_size * pango::SCALE);
-                d
-            });
-            layout.set_font_description(Some(desc));
-            // Worst-case label: max 5-digit coords
-            layout.set_text("-99999,-99999, 99999x99999");
-            let (tw, th) = layout.pixel_size();
-            // 10px offset from selection corner + text size + padding(2px)
-            (tw as f64 + 12.0, th as f64 + 12.0)
-        });
-
-        let clip_rect = if let Some(p) = self.prev_selection.as_ref() {
-            [
-                p[0].min(current_sel[0]) - border_margin,
-                p[1].min(current_sel[1]) - border_margin,
-                p[2].max(current_sel[2]) + text_margin_w,
-                p[3].max(current_sel[3]) + text_margin_h,
-            ]
-        } else {
-            [
-                current_sel[0] - border_margin,
-                current_sel[1] - border_margin,
-                current_sel[2] + text_margin_w,
-                current_sel[3] + text_margin_h,
-            ]
+            _ => None,
         };
 
-        let cx = clip_rect[0].max(0.0);
-        let cy = clip_rect[1].max(0.0);
-        let cw = (clip_rect[2] - cx).min(width as f64 - cx).max(0.0);
-        let ch = (clip_rect[3] - cy).min(height as f64 - cy).max(0.0);
+        let mut clip_rect = [
+            current_sel[0] - border_margin,
+            current_sel[1] - border_margin,
+            current_sel[2] + border_margin,
+            current_sel[3] + border_margin,
+        ];
+        if let Some(p) = self.prev_selection.as_ref() {
+            clip_rect = union_rect(
+                clip_rect,
+                [
+                    p[0] - border_margin,
+                    p[1] - border_margin,
+                    p[2] + border_margin,
+                    p[3] + border_margin,
+                ],
+            );
+        }
+        for label_rect in [self.prev_label, current_label].into_iter().flatten() {
+            clip_rect = union_rect(clip_rect, label_rect);
+        }
 
-        let dx = cx.floor() as i32;
-        let dy = cy.floor() as i32;
-        let dw = (cx + cw).ceil() as i32 - dx;
-        let dh = (cy + ch).ceil() as i32 - dy;
+        let active = self.style.inactive_color.is_none()
+            || (current_sel[2] >= 0.0
+                && current_sel[3] >= 0.0
+                && current_sel[0] <= width as f64
+                && current_sel[1] <= height as f64);
+        // the whole background changes with the state of the output
+        let redraw_all = redraw_all || self.prev_active != Some(active);
 
-        if !redraw_all {
-            cairoinfo.save().unwrap();
-            cairoinfo.rectangle(dx as f64, dy as f64, dw as f64, dh as f64);
-            cairoinfo.clip();
-        }
-        cairoinfo.set_operator(cairo::Operator::Source);
-        cairoinfo.set_source_rgba(
-            self.style.background_color.r,
-            self.style.background_color.g,
-            self.style.background_color.b,
-            self.style.background_color.a,
+        let guides = self.layout_guides(
+            pointer,
+            Position {
+                x: start_x,
+                y: start_y,
+            },
+            size,
         );
-        cairoinfo.paint().unwrap();
+        let current_guides = self.guide_rects(guides.as_ref());
+        let hud = self.layout_hud(size);
+        let damage: Vec<[i32; 4]> = std::iter::once(clip_rect)
+            .chain(current_guides.iter().copied())
+            .chain(self.prev_guides.iter().flatten().copied())
+            .chain(hud.iter().chain(&self.prev_hud).map(|item| item.rect))
+            .filter_map(|rect| damage_rect(rect, size))
+            .collect();
 
-        if !redraw_all {
-            cairoinfo.restore().unwrap();
+        cairoinfo.save().unwrap();
+        self.clip(&damage, redraw_all);
+        cairoinfo.set_operator(cairo::Operator::Source);
+        let background = self.background_color(active);
+        cairoinfo.set_source_rgba(background.r, background.g, background.b, background.a);
+        cairoinfo.paint().unwrap();
+        cairoinfo.restore().unwrap();
+        cairoinfo.set_operator(cairo::Operator::Source);
 
-            cairoinfo.set_operator(cairo::Operator::Source);
-        }
+        // only the boxes in the damage are repainted, their border may stick out of it
+        let visible = opt_boxes.map(|boxes| {
+            let mut visible = Vec::new();
+            let rects = match redraw_all {
+                true => vec![[0, 0, width, height]],
+                false => damage.clone(),
+            };
+            for [x, y, w, h] in rects {
+                let rect = [
+                    (x + start_x) as f64 - border_margin,
+                    (y + start_y) as f64 - border_margin,
+                    (x + w + start_x) as f64 + border_margin,
+                    (y + h + start_y) as f64 + border_margin,
+                ];
+                boxes.within(rect, &mut visible);
+            }
+            visible
+                .into_iter()
+                .map(|index| &boxes[index])
+                .collect::<Vec<_>>()
+        });
 
-        if let Some(boxes) = opt_boxes {
+        if let Some(boxes) = visible.as_ref() {
+            // the labels are drawn over, so the boxes are only redrawn where the labels are
+            cairoinfo.save().unwrap();
+            self.clip(&damage, redraw_all);
             for box_info in boxes {
                 let bstart_x = box_info.start_x - start_x as f64;
                 let bstart_y = box_info.start_y - start_y as f64;
-                let bend_x = box_info.end_x - start_x as f64;
-                let bend_y = box_info.end_x - start_y as f64;
-                let bwidth = bend_x - box_info.start_x;
-                let bheight = bend_y - box_info.start_y;
+                let bwidth = box_info.end_x - box_info.start_x;
+                let bheight = box_info.end_y - box_info.start_y;
                 cairoinfo.rectangle(bstart_x, bstart_y, bwidth, bheight);
-                cairoinfo.set_source_rgba(
-                    self.style.box_color.r,
-                    self.style.box_color.g,
-                    self.style.box_color.b,
-                    self.style.box_color.a,
-                );
+                let color = box_info.color.unwrap_or(self.style.box_color);
+                cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a);
                 cairoinfo.fill_preserve().unwrap();
                 cairoinfo.stroke().unwrap();
             }
+            cairoinfo.restore().unwrap();
+        }
+
+        let radius = self.style.corner_radius;
+        selection_path(cairoinfo, current_sel, 0.0, radius);
+        let fill = match self.style.box_hover_color {
+            Some(color) if self.hovered_box => color,
+            _ => self.style.foreground_color,
+        };
+        cairoinfo.set_source_rgba(fill.r, fill.g, fill.b, fill.a);
+        cairoinfo.fill().unwrap();
+
+        let weight = self.style.border_weight;
+        let inset = match self.style.border_position {
+            BorderPosition::Inner => weight / 2.0,
+            BorderPosition::Center => 0.0,
+            BorderPosition::Outer => -weight / 2.0,
+        };
+        selection_path(cairoinfo, current_sel, inset, radius);
+        let border = match self.clamped {
+            true => self.style.clamp_color,
+            false => self.style.border_text_color,
+        };
+        cairoinfo.set_source_rgba(border.r, border.g, border.b, border.a);
+        cairoinfo.set_line_width(weight);
+        if self.style.marching_ants {
+            let dash = match self.style.border_dash.is_empty() {
+                true => &ANTS_DASH[..],
+                false => &self.style.border_dash[..],
+            };
+            cairoinfo.set_dash(dash, -dash_offset);
+        } else {
+            cairoinfo.set_dash(&self.style.border_dash, 0.0);
+        }
+        cairoinfo.stroke().unwrap();
+        cairoinfo.set_dash(&[], 0.0);
+
+        if let Some(boxes) = visible.as_ref() {
+            cairoinfo.save().unwrap();
+            self.clip(&damage, redraw_all);
+            self.draw_box_labels(
+                boxes,
+                Position {
+                    x: start_x,
+                    y: start_y,
+                },
+            );
+            cairoinfo.restore().unwrap();
+        }
+
+        if self.style.composition_guides != CompositionGuides::default() {
+            cairoinfo.save().unwrap();
+            self.clip(&damage, redraw_all);
+            self.draw_composition_guides(current_sel);
+            cairoinfo.restore().unwrap();
+        }
+
+        if let Some(guides) = guides.as_ref() {
+            cairoinfo.save().unwrap();
+            self.clip(&damage, redraw_all);
+            self.draw_guides(guides);
+            cairoinfo.restore().unwrap();
+        }
+
+        if let (Some(text), Some([lx1, ly1, lx2, ly2])) = (label, current_label) {
+            let pangolayout = self.pango_layout.get().unwrap();
+            pangolayout.set_font_description(self.font_desc_bold.get());
+            pangolayout.set_text(text);
+            cairoinfo.save().unwrap();
+            cairoinfo.set_operator(cairo::Operator::Over);
+            rounded_rectangle(cairoinfo, lx1, ly1, lx2 - lx1, ly2 - ly1, (ly2 - ly1) / 2.0);
+            cairoinfo.set_source_rgba(
+                self.style.label_background_color.r,
+                self.style.label_background_color.g,
+                self.style.label_background_color.b,
+                self.style.label_background_color.a,
+            );
+            cairoinfo.fill().unwrap();
+            cairoinfo.set_source_rgba(
+                self.style.border_text_color.r,
+                self.style.border_text_color.g,
+                self.style.border_text_color.b,
+                self.style.border_text_color.a,
+            );
+            cairoinfo.move_to(lx1 + LABEL_PADDING, ly1 + LABEL_PADDING);
+            pangocairo::functions::show_layout(cairoinfo, pangolayout);
+            cairoinfo.restore().unwrap();
         }
 
-        let relate_start_x = start_pos_x - start_x as f64;
-        let relate_start_y = start_pos_y - start_y as f64;
-        let relate_end_x = end_pos.x - start_x as f64;
-        let relate_end_y = end_pos.y - start_y as f64;
-        let rlwidth = relate_end_x - relate_start_x;
-        let rlheight = relate_end_y - relate_start_y;
+        self.draw_hud(&hud);
 
-        cairoinfo.rectangle(relate_start_x, relate_start_y, rlwidth, rlheight);
+        self.prev_selection = Some(current_sel);
+        self.prev_label = current_label;
+        self.prev_guides = Some(current_guides);
+        self.prev_active = Some(active);
+        self.prev_hud = hud;
+        match redraw_all {
+            true => Damage::All,
+            false => Damage::Rects(damage),
+        }
+    }
+
+    /// Redraw what follows the pointer before the selection starts: the guides, and the
+    /// background when the output becomes active or inactive. The first drawing of the guides
+    /// covers the whole surface, so that the rulers show up.
+    pub fn draw_pointer(
+        &mut self,
+        pointer: Position<f64>,
+        start_position: Position,
+        size: Size,
+    ) -> Option<Damage> {
+        let active = self.style.inactive_color.is_none()
+            || ((start_position.x as f64..(start_position.x + size.width) as f64)
+                .contains(&pointer.x)
+                && (start_position.y as f64..(start_position.y + size.height) as f64)
+                    .contains(&pointer.y));
+        let guides = self.layout_guides(pointer, start_position, size);
+        let redraw_all =
+            (guides.is_some() && self.prev_guides.is_none()) || self.prev_active != Some(active);
+        let hud = self.layout_hud(size);
+        if guides.is_none() && !redraw_all && hud == self.prev_hud {
+            return None;
+        }
+        let current_guides = self.guide_rects(guides.as_ref());
+        let damage: Vec<[i32; 4]> = current_guides
+            .iter()
+            .chain(self.prev_guides.iter().flatten())
+            .copied()
+            .chain(hud.iter().chain(&self.prev_hud).map(|item| item.rect))
+            .filter_map(|rect| damage_rect(rect, size))
+            .collect();
+
+        let cairoinfo = &self.cairo_t;
+        cairoinfo.save().unwrap();
+        self.clip(&damage, redraw_all);
+        cairoinfo.set_operator(cairo::Operator::Source);
+        let background = self.background_color(active);
+        cairoinfo.set_source_rgba(background.r, background.g, background.b, background.a);
+        cairoinfo.paint().unwrap();
+        if let Some(guides) = guides.as_ref() {
+            self.draw_guides(guides);
+        }
+        self.draw_hud(&hud);
+        cairoinfo.restore().unwrap();
+
+        self.prev_guides = Some(current_guides);
+        self.prev_active = Some(active);
+        self.prev_hud = hud;
+        Some(match redraw_all {
+            true => Damage::All,
+            false => Damage::Rects(damage),
+        })
+    }
+
+    /// Draw the labels of the predefined boxes in their top left corner, cut to the box.
+    fn draw_box_labels(&self, boxes: &[&BoxInfo], start: Position) {
+        let cairoinfo = &self.cairo_t;
+        let layout = self.normal_layout();
+        let color = self.style.border_text_color;
+        for box_info in boxes {
+            let Some(label) = box_info.label.as_deref() else {
+                continue;
+            };
+            let x = box_info.start_x - start.x as f64;
+            let y = box_info.start_y - start.y as f64;
+            cairoinfo.save().unwrap();
+            cairoinfo.rectangle(
+                x,
+                y,
+                box_info.end_x - box_info.start_x,
+                box_info.end_y - box_info.start_y,
+            );
+            cairoinfo.clip();
+            cairoinfo.set_operator(cairo::Operator::Over);
+            cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a);
+            layout.set_text(label);
+            cairoinfo.move_to(x + LABEL_PADDING, y + LABEL_PADDING);
+            pangocairo::functions::show_layout(cairoinfo, layout);
+            cairoinfo.restore().unwrap();
+        }
+    }
+
+    /// the background of the output, dimmed when it has neither the selection nor the pointer
+    fn background_color(&self, active: bool) -> Color {
+        match (active, self.style.inactive_color) {
+            (false, Some(color)) => color,
+            _ => self.style.background_color,
+        }
+    }
+
+    /// Draw the info card of the hovered output, at the configured position of the output.
+    fn draw_screen_card(&self, card: &str, Size { width, height }: Size) {
+        let (card_w, card_h) = self.card_size(card);
+        let (width, height) = (width as f64, height as f64);
+        let (x, y) = match self.style.screen_card_position {
+            CardPosition::TopLeft => (CARD_MARGIN, CARD_MARGIN),
+            CardPosition::TopRight => (width - CARD_MARGIN - card_w, CARD_MARGIN),
+            CardPosition::BottomLeft => (CARD_MARGIN, height - CARD_MARGIN - card_h),
+            CardPosition::BottomRight => {
+                (width - CARD_MARGIN - card_w, height - CARD_MARGIN - card_h)
+            }
+            CardPosition::Center => ((width - card_w) / 2.0, (height - card_h) / 2.0),
+        };
+        self.draw_card(card, [x, y, x + card_w, y + card_h]);
+    }
+
+    /// the size of a card with this text, padding included
+    fn card_size(&self, text: &str) -> (f64, f64) {
+        let layout = self.normal_layout();
+        layout.set_text(text);
+        let (text_w, text_h) = layout.pixel_size();
+        (
+            text_w as f64 + 2.0 * CARD_PADDING,
+            text_h as f64 + 2.0 * CARD_PADDING,
+        )
+    }
+
+    /// Draw a card with this text in the rect `[x1, y1, x2, y2]`.
+    fn draw_card(&self, text: &str, [x1, y1, x2, y2]: [f64; 4]) {
+        let cairoinfo = &self.cairo_t;
+        let layout = self.normal_layout();
+        layout.set_text(text);
+        cairoinfo.save().unwrap();
+        cairoinfo.set_operator(cairo::Operator::Over);
+        rounded_rectangle(cairoinfo, x1, y1, x2 - x1, y2 - y1, CARD_PADDING);
         cairoinfo.set_source_rgba(
-            self.style.foreground_color.r,
-            self.style.foreground_color.g,
-            self.style.foreground_color.b,
-            self.style.foreground_color.a,
+            self.style.background_color.r,
+            self.style.background_color.g,
+            self.style.background_color.b,
+            self.style.background_color.a,
         );
-        cairoinfo.fill_preserve().unwrap();
+        cairoinfo.fill().unwrap();
         cairoinfo.set_source_rgba(
             self.style.border_text_color.r,
             self.style.border_text_color.g,
             self.style.border_text_color.b,
             self.style.border_text_color.a,
         );
-        cairoinfo.set_line_width(self.style.border_weight);
-        cairoinfo.stroke().unwrap();
+        cairoinfo.move_to(x1 + CARD_PADDING, y1 + CARD_PADDING);
+        pangocairo::functions::show_layout(cairoinfo, layout);
+        cairoinfo.restore().unwrap();
+    }
 
-        if draw_text {
-            let font_size = self.style.font_size;
-            let pangolayout = self
-                .pango_layout
-                .get_or_init(|| pangocairo::functions::create_layout(cairoinfo));
-            let desc = self.font_desc_bold.get_or_init(|| {
-                let mut d = pango::FontDescription::new();
-                d.set_family(self.style.font_name.as_str());
-                d.set_weight(pango::Weight::Bold);
-                d.set_size(font_size * pango::SCALE);
-                d
+    /// Lay out the status line at the bottom of the surface and the help panel at its center.
+    fn layout_hud(&self, Size { width, height }: Size) -> Vec<HudItem> {
+        let (width, height) = (width as f64, height as f64);
+        let mut items = Vec::new();
+        if let Some(status) = &self.hud.status {
+            let (w, h) = self.card_size(status);
+            let x = ((width - w) / 2.0).max(0.0);
+            let y = (height - CARD_MARGIN - h).max(0.0);
+            items.push(HudItem {
+                text: status.clone(),
+                rect: [x, y, x + w, y + h],
+            });
+        }
+        if let Some(help) = &self.hud.help {
+            let (w, h) = self.card_size(help);
+            let x = ((width - w) / 2.0).max(0.0);
+            let y = ((height - h) / 2.0).max(0.0);
+            items.push(HudItem {
+                text: help.clone(),
+                rect: [x, y, x + w, y + h],
             });
+        }
+        items
+    }
 
-            pangolayout.set_font_description(Some(desc));
+    fn draw_hud(&self, items: &[HudItem]) {
+        for HudItem { text, rect } in items {
+            self.draw_card(text, *rect);
+        }
+    }
+
+    /// Draw the identify card of the output with this index: a large number, then the name, the
+    /// description, the mode, the scale and the position, centered on the output.
+    pub fn draw_identify(&self, index: usize, info: &ScreenInfo) {
+        let cairoinfo = &self.cairo_t;
+        let Size { width, height } = info.get_size();
+        let Size {
+            width: mode_w,
+            height: mode_h,
+        } = info.get_wloutput_size();
+        let Position { x, y } = info.get_position();
+        let mut details = vec![
+            info.get_name().to_string(),
+            info.get_description().to_string(),
+            format!("{mode_w}x{mode_h} @ {:.2} Hz", info.get_refresh_rate()),
+            format!("scale {}, logical {width}x{height}", info.get_scale()),
+            format!("position {x},{y}"),
+        ];
+        if info.get_transform() != Transform::Normal {
+            details.push(format!(
+                "transform {}",
+                transform_name(info.get_transform())
+            ));
+        }
+        details.retain(|line| !line.is_empty());
+        let details = details.join("\n");
+
+        cairoinfo.set_operator(cairo::Operator::Source);
+        let background = self.style.background_color;
+        cairoinfo.set_source_rgba(background.r, background.g, background.b, background.a);
+        cairoinfo.paint().unwrap();
+        cairoinfo.set_operator(cairo::Operator::Over);
+
+        let layout = self.layout();
+        let mut number_desc = pango::FontDescription::new();
+        number_desc.set_family(self.style.font_name.as_str());
+        number_desc.set_weight(pango::Weight::Bold);
+        number_desc.set_absolute_size(width.min(height) as f64 / 4.0 * pango::SCALE as f64);
+        layout.set_font_description(Some(&number_desc));
+        layout.set_text(&(index + 1).to_string());
+        let (number_w, number_h) = layout.pixel_size();
+
+        let mut details_desc = pango::FontDescription::new();
+        details_desc.set_family(self.style.font_name.as_str());
+        details_desc.set_size(self.style.font_size * 2 * pango::SCALE);
+        layout.set_font_description(Some(&details_desc));
+        layout.set_alignment(pango::Alignment::Center);
+        layout.set_text(&details);
+        let (details_w, details_h) = layout.pixel_size();
+
+        let card_w = number_w.max(details_w) as f64 + 4.0 * IDENTIFY_PADDING;
+        let card_h = (number_h + details_h) as f64 + 3.0 * IDENTIFY_PADDING;
+        let card_x = (width as f64 - card_w) / 2.0;
+        let card_y = (height as f64 - card_h) / 2.0;
+        rounded_rectangle(
+            cairoinfo,
+            card_x,
+            card_y,
+            card_w,
+            card_h,
+            2.0 * IDENTIFY_PADDING,
+        );
+        let card = self.style.label_background_color;
+        cairoinfo.set_source_rgba(card.r, card.g, card.b, card.a);
+        cairoinfo.fill().unwrap();
+
+        let text = self.style.border_text_color;
+        cairoinfo.set_source_rgba(text.r, text.g, text.b, text.a);
+        cairoinfo.move_to(
+            (width as f64 - details_w as f64) / 2.0,
+            card_y + 2.0 * IDENTIFY_PADDING + number_h as f64,
+        );
+        pangocairo::functions::show_layout(cairoinfo, layout);
+
+        layout.set_alignment(pango::Alignment::Left);
+        layout.set_font_description(Some(&number_desc));
+        layout.set_text(&(index + 1).to_string());
+        cairoinfo.move_to(
+            (width as f64 - number_w as f64) / 2.0,
+            card_y + IDENTIFY_PADDING,
+        );
+        pangocairo::functions::show_layout(cairoinfo, layout);
+    }
+
+    /// restrict the drawing to the damaged rects, unless the whole surface is redrawn
+    fn clip(&self, damage: &[[i32; 4]], redraw_all: bool) {
+        if redraw_all {
+            return;
+        }
+        for [x, y, w, h] in damage {
+            self.cairo_t
+                .rectangle(*x as f64, *y as f64, *w as f64, *h as f64);
+        }
+        self.cairo_t.clip();
+    }
 
-            let text = format!(
-                "{},{}, {}x{}",
-                start_pos_x as i32,
-                start_pos_y as i32,
-                (end_pos.x - start_pos_x) as i32,
-                (end_pos.y - start_pos_y) as i32
+    /// Lay out the guides of the pointer on this surface, `None` when they are disabled.
+    fn layout_guides(
+        &self,
+        Position { x, y }: Position<f64>,
+        start: Position,
+        size: Size,
+    ) -> Option<Guides> {
+        if !self.style.crosshair && !self.style.rulers {
+            return None;
+        }
+        let x = x - start.x as f64;
+        let y = y - start.y as f64;
+        let mut readouts = Vec::new();
+        let inside = x >= 0.0 && y >= 0.0 && x < size.width as f64 && y < size.height as f64;
+        if self.style.rulers && inside {
+            let layout = self.normal_layout();
+            let mut readout = |text: String, x: f64, y: f64| {
+                layout.set_text(&text);
+                let (tw, th) = layout.pixel_size();
+                let w = tw as f64 + 2.0 * LABEL_PADDING;
+                let h = th as f64 + 2.0 * LABEL_PADDING;
+                let x = x.min(size.width as f64 - w).max(0.0);
+                let y = y.min(size.height as f64 - h).max(0.0);
+                readouts.push((text, [x, y, x + w, y + h]));
+            };
+            readout(
+                (x as i32 + start.x).to_string(),
+                x + LABEL_PADDING,
+                RULER_SIZE + 2.0,
             );
+            readout(
+                (y as i32 + start.y).to_string(),
+                RULER_SIZE + 2.0,
+                y + LABEL_PADDING,
+            );
+        }
+        Some(Guides {
+            x,
+            y,
+            start,
+            size,
+            readouts,
+        })
+    }
 
-            pangolayout.set_text(text.as_str());
-            cairoinfo.save().unwrap();
-            cairoinfo.move_to(relate_end_x + 10., relate_end_y + 10.);
-            pangocairo::functions::show_layout(cairoinfo, pangolayout);
-            cairoinfo.restore().unwrap();
+    /// the rects covered by the parts of the guides that follow the pointer
+    fn guide_rects(&self, guides: Option<&Guides>) -> Vec<[f64; 4]> {
+        let Some(Guides {
+            x,
+            y,
+            size,
+            readouts,
+            ..
+        }) = guides
+        else {
+            return Vec::new();
+        };
+        let (width, height) = (size.width as f64, size.height as f64);
+        let (x, y) = (x.floor(), y.floor());
+        let mut rects = Vec::new();
+        let (cross_x, cross_y) = match self.style.crosshair {
+            true => (height, width),
+            false if !readouts.is_empty() => (RULER_SIZE, RULER_SIZE),
+            false => (0.0, 0.0),
+        };
+        if (0.0..width).contains(&x) && cross_x > 0.0 {
+            rects.push([x - 1.0, 0.0, x + 2.0, cross_x]);
         }
+        if (0.0..height).contains(&y) && cross_y > 0.0 {
+            rects.push([0.0, y - 1.0, cross_y, y + 2.0]);
+        }
+        rects.extend(readouts.iter().map(|(_, rect)| *rect));
+        rects
+    }
 
-        self.wl_surface.attach(Some(&self.buffer), 0, 0);
-        if redraw_all {
-            self.wl_surface.damage(0, 0, width, height);
-        } else {
-            self.wl_surface.damage(dx, dy, dw, dh);
+    fn draw_guides(&self, guides: &Guides) {
+        let cairoinfo = &self.cairo_t;
+        let Guides {
+            x,
+            y,
+            start,
+            size,
+            readouts,
+        } = guides;
+        let (width, height) = (size.width as f64, size.height as f64);
+        let color = self.style.border_text_color;
+        cairoinfo.set_operator(cairo::Operator::Over);
+        cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a);
+        cairoinfo.set_line_width(1.0);
+
+        if self.style.rulers {
+            self.draw_rulers(*start, *size);
         }
-        self.wl_surface.commit();
 
-        self.prev_selection = Some(current_sel);
+        let (x, y) = (x.floor() + 0.5, y.floor() + 0.5);
+        let (cross_x, cross_y) = match self.style.crosshair {
+            true => (height, width),
+            false if !readouts.is_empty() => (RULER_SIZE, RULER_SIZE),
+            false => (0.0, 0.0),
+        };
+        cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a);
+        if (0.0..width).contains(&x) && cross_x > 0.0 {
+            cairoinfo.move_to(x, 0.0);
+            cairoinfo.line_to(x, cross_x);
+        }
+        if (0.0..height).contains(&y) && cross_y > 0.0 {
+            cairoinfo.move_to(0.0, y);
+            cairoinfo.line_to(cross_y, y);
+        }
+        cairoinfo.stroke().unwrap();
+
+        let layout = self.normal_layout();
+        for (text, [x1, y1, x2, y2]) in readouts {
+            rounded_rectangle(cairoinfo, *x1, *y1, x2 - x1, y2 - y1, (y2 - y1) / 2.0);
+            cairoinfo.set_source_rgba(
+                self.style.label_background_color.r,
+                self.style.label_background_color.g,
+                self.style.label_background_color.b,
+                self.style.label_background_color.a,
+            );
+            cairoinfo.fill().unwrap();
+            cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a);
+            layout.set_text(text);
+            cairoinfo.move_to(x1 + LABEL_PADDING, y1 + LABEL_PADDING);
+            pangocairo::functions::show_layout(cairoinfo, layout);
+        }
+    }
+
+    /// Draw the rulers along the top and left edges, graduated in global logical coordinates.
+    /// Only the ticks in the clip are drawn.
+    fn draw_rulers(&self, start: Position, Size { width, height }: Size) {
+        let cairoinfo = &self.cairo_t;
+        let (clip_x1, clip_y1, clip_x2, clip_y2) = cairoinfo.clip_extents().unwrap();
+        let layout = self.normal_layout();
+        let tick_len = |pos: i32| match pos {
+            pos if pos % 100 == 0 => RULER_SIZE,
+            pos if pos % 50 == 0 => RULER_SIZE / 2.0,
+            _ => RULER_SIZE / 4.0,
+        };
+        // the numbers reach past their tick
+        let visible = |pos: f64, clip1: f64, clip2: f64| pos >= clip1 - 100.0 && pos <= clip2;
+
+        let first = start.x.div_euclid(RULER_STEP) * RULER_STEP;
+        for gx in (first..start.x + width).step_by(RULER_STEP as usize) {
+            let x = (gx - start.x) as f64 + 0.5;
+            if clip_y1 > RULER_SIZE || !visible(x, clip_x1, clip_x2) {
+                continue;
+            }
+            cairoinfo.move_to(x, 0.0);
+            cairoinfo.line_to(x, tick_len(gx));
+            cairoinfo.stroke().unwrap();
+            if gx % 100 == 0 {
+                layout.set_text(&gx.to_string());
+                cairoinfo.move_to(x + 2.0, RULER_SIZE / 4.0);
+                pangocairo::functions::show_layout(cairoinfo, layout);
+            }
+        }
+
+        let first = start.y.div_euclid(RULER_STEP) * RULER_STEP;
+        for gy in (first..start.y + height).step_by(RULER_STEP as usize) {
+            let y = (gy - start.y) as f64 + 0.5;
+            if clip_x1 > RULER_SIZE + 100.0 || !visible(y, clip_y1, clip_y2) {
+                continue;
+            }
+            cairoinfo.move_to(0.0, y);
+            cairoinfo.line_to(tick_len(gy), y);
+            cairoinfo.stroke().unwrap();
+            if gy % 100 == 0 {
+                layout.set_text(&gy.to_string());
+                cairoinfo.move_to(RULER_SIZE / 4.0, y + 2.0);
+                pangocairo::functions::show_layout(cairoinfo, layout);
+            }
+        }
+    }
+
+    /// Draw the composition guides inside the selection `[x1, y1, x2, y2]`.
+    fn draw_composition_guides(&self, [x1, y1, x2, y2]: [f64; 4]) {
+        let cairoinfo = &self.cairo_t;
+        let CompositionGuides {
+            thirds,
+            center,
+            title_safe,
+            action_safe,
+        } = self.style.composition_guides;
+        let (w, h) = (x2 - x1, y2 - y1);
+        if w < 1.0 || h < 1.0 {
+            return;
+        }
+        let color = self.style.border_text_color;
+        cairoinfo.rectangle(x1, y1, w, h);
+        cairoinfo.clip();
+        cairoinfo.set_operator(cairo::Operator::Over);
+        cairoinfo.set_source_rgba(color.r, color.g, color.b, color.a * GUIDE_ALPHA);
+        cairoinfo.set_line_width(1.0);
+
+        if thirds {
+            for i in 1..3 {
+                let x = (x1 + w * i as f64 / 3.0).floor() + 0.5;
+                let y = (y1 + h * i as f64 / 3.0).floor() + 0.5;
+                cairoinfo.move_to(x, y1);
+                cairoinfo.line_to(x, y2);
+                cairoinfo.move_to(x1, y);
+                cairoinfo.line_to(x2, y);
+            }
+            cairoinfo.stroke().unwrap();
+        }
+        if center {
+            let x = (x1 + w / 2.0).floor() + 0.5;
+            let y = (y1 + h / 2.0).floor() + 0.5;
+            let arm = (w.min(h) / 10.0).clamp(4.0, 20.0);
+            cairoinfo.move_to(x - arm, y);
+            cairoinfo.line_to(x + arm, y);
+            cairoinfo.move_to(x, y - arm);
+            cairoinfo.line_to(x, y + arm);
+            cairoinfo.stroke().unwrap();
+        }
+        cairoinfo.set_dash(&[4.0, 4.0], 0.0);
+        for (enabled, margin) in [(action_safe, ACTION_SAFE), (title_safe, TITLE_SAFE)] {
+            if !enabled {
+                continue;
+            }
+            let mx = (w * margin).round();
+            let my = (h * margin).round();
+            cairoinfo.rectangle(
+                x1 + mx + 0.5,
+                y1 + my + 0.5,
+                (w - 2.0 * mx - 1.0).max(0.0),
+                (h - 2.0 * my - 1.0).max(0.0),
+            );
+            cairoinfo.stroke().unwrap();
+        }
+        cairoinfo.set_dash(&[], 0.0);
+    }
+
+    /// the shared pango layout, with a tab stop wide enough for the keys of the help panel
+    fn layout(&self) -> &pango::Layout {
+        self.pango_layout.get_or_init(|| {
+            let layout = pangocairo::functions::create_layout(&self.cairo_t);
+            let mut tabs = pango::TabArray::new(1, true);
+            tabs.set_tab(
+                0,
+                pango::TabAlign::Left,
+                HELP_KEYS_WIDTH * self.style.font_size,
+            );
+            layout.set_tabs(Some(&tabs));
+            layout
+        })
+    }
+
+    /// the shared pango layout, set to the normal font
+    fn normal_layout(&self) -> &pango::Layout {
+        let layout = self.layout();
+        let desc = self.font_desc_normal.get_or_init(|| {
+            let mut d = pango::FontDescription::new();
+            d.set_family(self.style.font_name.as_str());
+            d.set_weight(pango::Weight::Normal);
+            d.set_size(self.style.font_size * pango::SCALE);
+            d
+        });
+        layout.set_font_description(Some(desc));
+        layout
     }
 }
 
+/// The guides of the pointer on a surface
+struct Guides {
+    /// the pointer, relative to the surface
+    x: f64,
+    y: f64,
+    start: Position,
+    size: Size,
+    /// the text of the readouts on the rulers, with the rect they cover
+    readouts: Vec<(String, [f64; 4])>,
+}
+
+/// the opacity of the composition guides, relative to the border color
+const GUIDE_ALPHA: f64 = 0.6;
+/// margin of the action-safe area on each side, relative to the selection (EBU R 95)
+const ACTION_SAFE: f64 = 0.035;
+/// margin of the title-safe area on each side, relative to the selection (EBU R 95)
+const TITLE_SAFE: f64 = 0.05;
+
+/// the dash pattern of the marching ants, when no other one is set
+const ANTS_DASH: [f64; 2] = [6.0, 6.0];
+
+/// space between the info card of the hovered output and its edges
+const CARD_MARGIN: f64 = 10.0;
+/// space around the text of the info card
+const CARD_PADDING: f64 = 8.0;
+
+/// the width of the column of the keys in the help panel, in font sizes
+const HELP_KEYS_WIDTH: i32 = 5;
+
+/// space around the texts of the identify card
+const IDENTIFY_PADDING: f64 = 16.0;
+
+/// the thickness of the rulers
+const RULER_SIZE: f64 = 24.0;
+/// the distance between two ticks of the rulers
+const RULER_STEP: i32 = 10;
+
+/// The rect rounded out to whole pixels and kept inside the surface, `None` when it is empty.
+fn damage_rect([x1, y1, x2, y2]: [f64; 4], Size { width, height }: Size) -> Option<[i32; 4]> {
+    let x1 = x1.max(0.0).floor() as i32;
+    let y1 = y1.max(0.0).floor() as i32;
+    let x2 = (x2.ceil() as i32).min(width);
+    let y2 = (y2.ceil() as i32).min(height);
+    (x2 > x1 && y2 > y1).then_some([x1, y1, x2 - x1, y2 - y1])
+}
+
+/// distance between the selection and its label
+const LABEL_OFFSET: f64 = 10.0;
+/// space between the text of the label and the edge of its pill
+const LABEL_PADDING: f64 = 4.0;
+
+/// Place a label of the given size at a corner of the selection `[x1, y1, x2, y2]`, flipped to the
+/// other side of the selection when it would leave the output, then kept inside the output. There
+/// is no label on the outputs the selection does not touch.
+fn label_rect(
+    [x1, y1, x2, y2]: [f64; 4],
+    (label_w, label_h): (f64, f64),
+    corner: LabelCorner,
+    (width, height): (f64, f64),
+) -> Option<[f64; 4]> {
+    if x2 < 0.0 || y2 < 0.0 || x1 > width || y1 > height {
+        return None;
+    }
+    let right = matches!(corner, LabelCorner::TopRight | LabelCorner::BottomRight);
+    let bottom = matches!(corner, LabelCorner::BottomLeft | LabelCorner::BottomRight);
+
+    let after_x = x2 + LABEL_OFFSET;
+    let before_x = x1 - LABEL_OFFSET - label_w;
+    let mut x = if right { after_x } else { before_x };
+    if right && x + label_w > width {
+        x = before_x;
+    } else if !right && x < 0.0 {
+        x = after_x;
+    }
+
+    let after_y = y2 + LABEL_OFFSET;
+    let before_y = y1 - LABEL_OFFSET - label_h;
+    let mut y = if bottom { after_y } else { before_y };
+    if bottom && y + label_h > height {
+        y = before_y;
+    } else if !bottom && y < 0.0 {
+        y = after_y;
+    }
+
+    let x = x.min(width - label_w).max(0.0);
+    let y = y.min(height - label_h).max(0.0);
+    Some([x, y, x + label_w, y + label_h])
+}
+
+/// The path of the selection `[x1, y1, x2, y2]` shrunk by `inset` on each side, negative to grow
+/// it, with the corners rounded by `radius`.
+fn selection_path(cairoinfo: &Context, [x1, y1, x2, y2]: [f64; 4], inset: f64, radius: f64) {
+    let width = (x2 - x1 - 2.0 * inset).max(0.0);
+    let height = (y2 - y1 - 2.0 * inset).max(0.0);
+    let radius = (radius - inset).max(0.0);
+    if radius > 0.0 {
+        rounded_rectangle(cairoinfo, x1 + inset, y1 + inset, width, height, radius);
+    } else {
+        cairoinfo.rectangle(x1 + inset, y1 + inset, width, height);
+    }
+}
+
+fn union_rect(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    [
+        a[0].min(b[0]),
+        a[1].min(b[1]),
+        a[2].max(b[2]),
+        a[3].max(b[3]),
+    ]
+}
+
+fn rounded_rectangle(cairoinfo: &Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    let degrees = std::f64::consts::PI / 180.0;
+    cairoinfo.new_sub_path();
+    cairoinfo.arc(x + width - radius, y + radius, radius, -90.0 * degrees, 0.0);
+    cairoinfo.arc(
+        x + width - radius,
+        y + height - radius,
+        radius,
+        0.0,
+        90.0 * degrees,
+    );
+    cairoinfo.arc(
+        x + radius,
+        y + height - radius,
+        radius,
+        90.0 * degrees,
+        180.0 * degrees,
+    );
+    cairoinfo.arc(
+        x + radius,
+        y + radius,
+        radius,
+        180.0 * degrees,
+        270.0 * degrees,
+    );
+    cairoinfo.close_path();
+}
+
 #[derive(Debug)]
 pub struct UiInit {
     pub context: Context,
@@ -319,3 +1166,176 @@ pub fn draw_ui(
         stride,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cairo::ImageSurface;
+
+    use super::*;
+
+    /// a label of 100x20 on an output of 1000x500
+    fn label(selection: [f64; 4], corner: LabelCorner) -> Option<[f64; 4]> {
+        label_rect(selection, (100.0, 20.0), corner, (1000.0, 500.0))
+    }
+
+    #[test]
+    fn label_at_the_requested_corner() {
+        let selection = [400.0, 200.0, 600.0, 300.0];
+        assert_eq!(
+            label(selection, LabelCorner::TopLeft),
+            Some([290.0, 170.0, 390.0, 190.0])
+        );
+        assert_eq!(
+            label(selection, LabelCorner::TopRight),
+            Some([610.0, 170.0, 710.0, 190.0])
+        );
+        assert_eq!(
+            label(selection, LabelCorner::BottomLeft),
+            Some([290.0, 310.0, 390.0, 330.0])
+        );
+        assert_eq!(
+            label(selection, LabelCorner::BottomRight),
+            Some([610.0, 310.0, 710.0, 330.0])
+        );
+    }
+
+    #[test]
+    fn label_flips_at_each_edge() {
+        use LabelCorner::*;
+        let cases = [
+            // left and right edges
+            ([50.0, 200.0, 150.0, 300.0], TopLeft, [160.0, 170.0]),
+            ([850.0, 200.0, 950.0, 300.0], BottomRight, [740.0, 310.0]),
+            // top and bottom edges
+            ([400.0, 20.0, 600.0, 300.0], TopLeft, [290.0, 310.0]),
+            ([400.0, 400.0, 600.0, 480.0], BottomRight, [610.0, 370.0]),
+            // corners, flipped on both axes
+            ([0.0, 0.0, 100.0, 100.0], TopLeft, [110.0, 110.0]),
+            ([900.0, 0.0, 1000.0, 100.0], TopRight, [790.0, 110.0]),
+            ([0.0, 400.0, 100.0, 500.0], BottomLeft, [110.0, 370.0]),
+            ([900.0, 400.0, 1000.0, 500.0], BottomRight, [790.0, 370.0]),
+            // crossing the edge of the output
+            ([-50.0, 100.0, 50.0, 200.0], TopLeft, [60.0, 70.0]),
+        ];
+        for (selection, corner, [x, y]) in cases {
+            assert_eq!(
+                label(selection, corner),
+                Some([x, y, x + 100.0, y + 20.0]),
+                "{selection:?} {corner:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn label_stays_on_the_output() {
+        // no side has room, the label is kept inside
+        assert_eq!(
+            label([0.0, 0.0, 1000.0, 500.0], LabelCorner::TopLeft),
+            Some([900.0, 480.0, 1000.0, 500.0])
+        );
+        assert_eq!(
+            label([0.0, 0.0, 1000.0, 500.0], LabelCorner::BottomRight),
+            Some([0.0, 0.0, 100.0, 20.0])
+        );
+        // and is not drawn on the outputs the selection does not touch
+        assert_eq!(
+            label([1100.0, 0.0, 1200.0, 100.0], LabelCorner::TopLeft),
+            None
+        );
+        assert_eq!(
+            label([-200.0, -200.0, -1.0, -1.0], LabelCorner::BottomRight),
+            None
+        );
+    }
+
+    #[test]
+    fn damage_is_rounded_out_and_clipped() {
+        let size = Size {
+            width: 100,
+            height: 100,
+        };
+        assert_eq!(
+            damage_rect([0.5, 1.5, 10.2, 20.7], size),
+            Some([0, 1, 11, 20])
+        );
+        assert_eq!(
+            damage_rect([-5.0, -5.0, 5.0, 5.0], size),
+            Some([0, 0, 5, 5])
+        );
+        assert_eq!(
+            damage_rect([95.0, 95.0, 200.0, 200.0], size),
+            Some([95, 95, 5, 5])
+        );
+        assert_eq!(damage_rect([10.0, 10.0, 10.0, 20.0], size), None);
+        assert_eq!(damage_rect([100.0, 0.0, 150.0, 50.0], size), None);
+        assert_eq!(damage_rect([-50.0, -50.0, -1.0, -1.0], size), None);
+    }
+
+    fn rects(damage: Damage) -> Vec<[i32; 4]> {
+        match damage {
+            Damage::All => panic!("the whole surface is damaged"),
+            Damage::Rects(rects) => rects,
+        }
+    }
+
+    /// whether `rect`, as `[x, y, width, height]`, is inside one of `damage`
+    fn covered(damage: &[[i32; 4]], [x, y, w, h]: [i32; 4]) -> bool {
+        damage
+            .iter()
+            .any(|[dx, dy, dw, dh]| *dx <= x && *dy <= y && dx + dw >= x + w && dy + dh >= y + h)
+    }
+
+    #[test]
+    fn damage_covers_the_previous_and_the_current_frame() {
+        let size = Size {
+            width: 400,
+            height: 300,
+        };
+        let surface = ImageSurface::create(Format::ARgb32, size.width, size.height).unwrap();
+        let mut canvas = Canvas::new(Context::new(&surface).unwrap(), Style::default());
+        let draw = |canvas: &mut Canvas,
+                    [x1, y1, x2, y2]: [f64; 4],
+                    label: Option<&str>,
+                    redraw_all: bool| {
+            canvas.draw_selection(
+                Position { x: x1, y: y1 },
+                Position { x: x2, y: y2 },
+                Position { x: 0, y: 0 },
+                size,
+                Position { x: x2, y: y2 },
+                label,
+                None,
+                0.0,
+                redraw_all,
+            )
+        };
+        assert!(matches!(
+            draw(&mut canvas, [10.0, 10.0, 50.0, 50.0], None, true),
+            Damage::All
+        ));
+
+        // the border of 1 and a margin of 2 around both selections
+        let damage = rects(draw(&mut canvas, [100.0, 100.0, 150.0, 120.0], None, false));
+        assert_eq!(damage, [[7, 7, 146, 116]]);
+
+        // dragged back up and left
+        let damage = rects(draw(&mut canvas, [20.0, 30.0, 60.0, 40.0], None, false));
+        assert_eq!(damage, [[17, 27, 136, 96]]);
+
+        let damage = rects(draw(
+            &mut canvas,
+            [200.0, 200.0, 250.0, 250.0],
+            Some("50x50"),
+            false,
+        ));
+        let label = canvas.prev_label.expect("the label is drawn");
+        let label = damage_rect(label, size).unwrap();
+        assert!(covered(&damage, label), "{damage:?} {label:?}");
+        assert!(covered(&damage, [17, 27, 236, 226]), "{damage:?}");
+
+        // the label of the previous frame is cleared
+        let damage = rects(draw(&mut canvas, [200.0, 200.0, 210.0, 210.0], None, false));
+        assert!(covered(&damage, label), "{damage:?} {label:?}");
+        assert_eq!(canvas.prev_label, None);
+    }
+}
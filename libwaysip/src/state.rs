@@ -4,7 +4,7 @@ use wayland_client::{
     QueueHandle,
     protocol::{
         wl_buffer::WlBuffer,
-        wl_output::WlOutput,
+        wl_output::{Subpixel, Transform, WlOutput},
         wl_shm::{self, WlShm},
         wl_surface::WlSurface,
     },
@@ -14,17 +14,24 @@ use wayland_protocols::{
     wp::cursor_shape::v1::client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
     xdg::xdg_output::zv1::client::zxdg_output_v1,
 };
-use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::ZwlrLayerSurfaceV1;
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    output_management::v1::client::zwlr_output_head_v1::ZwlrOutputHeadV1,
+};
 
 use crate::{
-    Position, Size, Style,
+    Bounds, BoxPolicy, Color, Position, Size, SizeLimits, StampAnchor,
+    boxes::BoxSet,
+    dispatch::Keymap,
     error::BoxInfoError,
-    render::{self, UiInit},
+    format::AreaFields,
+    render::{self, Canvas, Hud, UiInit},
 };
 
 /// You are allow to choose three actions of waysip, include area selection, point selection, and
 /// select screen
 #[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
 pub enum SelectionType {
     #[default]
     Area,
@@ -33,6 +40,15 @@ pub enum SelectionType {
     PredefinedBoxes,
     /// Combined mode: single click behaves like output selection, drag behaves like dimensions
     DimensionsOrOutput,
+    /// Combined mode: single click selects the predefined box under the pointer, drag behaves
+    /// like dimensions
+    BoxesOrDimensions,
+    /// An area of this width and height follows the pointer, a click places it. The wheel scales
+    /// it, keeping its aspect ratio
+    FixedSize(i32, i32),
+    /// Show a card with the index, name, mode, scale and position on every output, until a key
+    /// or a button is pressed. Nothing is selected
+    Identify,
 }
 
 #[derive(Debug, Clone)]
@@ -59,12 +75,48 @@ impl ZXdgOutputInfo {
     }
 }
 
+/// The state of a `zwlr_output_head_v1`, used to complete the [WlOutputInfo] with the same name
+#[derive(Debug, Clone)]
+pub(crate) struct OutputHeadInfo {
+    pub head: ZwlrOutputHeadV1,
+    pub name: String,
+    pub scale: Option<f64>,
+    pub enabled: Option<bool>,
+}
+
+impl OutputHeadInfo {
+    pub fn new(head: ZwlrOutputHeadV1) -> Self {
+        Self {
+            head,
+            name: "".to_string(),
+            scale: None,
+            enabled: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WlOutputInfo {
     pub output: WlOutput,
     pub description: String,
     pub name: String,
     pub size: Size,
+    pub make: String,
+    pub model: String,
+    /// physical size of the output in millimeters
+    pub physical_size: Size,
+    pub subpixel: Subpixel,
+    pub transform: Transform,
+    /// refresh rate of the current mode in mHz
+    pub refresh: i32,
+    /// whether the current mode is the preferred one of the output
+    pub preferred_mode: bool,
+    /// integer scale sent by wl_output
+    pub scale: i32,
+    /// exact scale, only known when zwlr_output_manager_v1 is available
+    pub fractional_scale: Option<f64>,
+    /// only known when zwlr_output_manager_v1 is available
+    pub enabled: Option<bool>,
     pub xdg_output_info: OnceCell<ZXdgOutputInfo>,
 }
 
@@ -91,6 +143,19 @@ impl WlOutputInfo {
                 width: 0,
                 height: 0,
             },
+            make: "".to_string(),
+            model: "".to_string(),
+            physical_size: Size {
+                width: 0,
+                height: 0,
+            },
+            subpixel: Subpixel::Unknown,
+            transform: Transform::Normal,
+            refresh: 0,
+            preferred_mode: false,
+            scale: 1,
+            fractional_scale: None,
+            enabled: None,
             xdg_output_info: OnceCell::new(),
         }
     }
@@ -103,6 +168,16 @@ impl WlOutputInfo {
             output_size: self.size,
             wl_output: self.output.clone(),
             screen_size: xdg_output_info.size,
+            make: self.make.clone(),
+            model: self.model.clone(),
+            physical_size: self.physical_size,
+            subpixel: self.subpixel,
+            transform: self.transform,
+            refresh: self.refresh,
+            preferred_mode: self.preferred_mode,
+            scale: self.scale,
+            fractional_scale: self.fractional_scale,
+            enabled: self.enabled,
         }
     }
     pub fn get_output(&self) -> &WlOutput {
@@ -132,6 +207,16 @@ pub struct ScreenInfo {
     pub output_size: Size,
     pub name: String,
     pub description: String,
+    pub make: String,
+    pub model: String,
+    pub physical_size: Size,
+    pub subpixel: Subpixel,
+    pub transform: Transform,
+    pub refresh: i32,
+    pub preferred_mode: bool,
+    pub scale: i32,
+    pub fractional_scale: Option<f64>,
+    pub enabled: Option<bool>,
 }
 
 impl ScreenInfo {
@@ -164,11 +249,95 @@ impl ScreenInfo {
     pub fn get_position(&self) -> Position {
         self.position
     }
+
+    /// get the manufacturer of the screen
+    pub fn get_make(&self) -> &str {
+        &self.make
+    }
+
+    /// get the model of the screen
+    pub fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    /// get the physical size of the screen in millimeters
+    pub fn get_physical_size(&self) -> Size {
+        self.physical_size
+    }
+
+    /// get the subpixel orientation of the screen
+    pub fn get_subpixel(&self) -> Subpixel {
+        self.subpixel
+    }
+
+    /// get the transform applied to the screen
+    pub fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// get the refresh rate of the current mode in mHz
+    pub fn get_refresh(&self) -> i32 {
+        self.refresh
+    }
+
+    /// get the refresh rate of the current mode in Hz
+    pub fn get_refresh_rate(&self) -> f64 {
+        self.refresh as f64 / 1000.0
+    }
+
+    /// tell if the current mode is the preferred mode of the screen
+    pub fn is_preferred_mode(&self) -> bool {
+        self.preferred_mode
+    }
+
+    /// get the scale of the screen, the fractional one when the compositor provides it
+    pub fn get_scale(&self) -> f64 {
+        self.fractional_scale.unwrap_or(self.scale as f64)
+    }
+
+    /// get the integer scale sent by wl_output
+    pub fn get_integer_scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// get the exact scale, only available with zwlr_output_manager_v1
+    pub fn get_fractional_scale(&self) -> Option<f64> {
+        self.fractional_scale
+    }
+
+    /// tell if the screen is enabled, only available with zwlr_output_manager_v1
+    pub fn is_enabled(&self) -> Option<bool> {
+        self.enabled
+    }
 }
 
+/// the scroll of a notch of the wheel, as sent by most compositors
+const SCROLL_STEP: f64 = 10.0;
+/// how much a notch of the wheel scales a fixed-size selection
+const STAMP_SCALE_STEP: f64 = 1.1;
+/// how far the pointer moves while pressed before a click becomes a drag
+const DRAG_THRESHOLD: f64 = 5.0;
+/// the typed label prefix starts over after this long without a key
+const TYPE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// A direction of the arrow keys
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// speed of the marching ants, in pixels per second
+const ANTS_SPEED: f64 = 30.0;
+/// the marching ants stop after this long without input, to save power
+const ANTS_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Debug)]
 pub struct WaysipState {
     pub(crate) wloutput_infos: Vec<WlOutputInfo>,
+    pub(crate) output_heads: Vec<OutputHeadInfo>,
     pub(crate) running: bool,
     pub(crate) selection_type: SelectionType,
     pub(crate) wl_surfaces: Vec<LayerSurfaceInfo>,
@@ -179,20 +348,63 @@ pub struct WaysipState {
     pub(crate) cursor_manager: Option<WpCursorShapeManagerV1>,
     pub(crate) shm: Option<WlShm>,
     pub(crate) qh: Option<QueueHandle<Self>>,
-    pub(crate) predefined_boxes: Option<Vec<BoxInfo>>,
+    pub(crate) predefined_boxes: Option<BoxSet>,
+    pub(crate) box_policy: BoxPolicy,
+    /// index of the predefined box under the pointer
+    pub(crate) selected_box: Option<usize>,
+    /// the predefined boxes under the pointer, in the order of the policy
+    box_candidates: Vec<usize>,
+    /// the buffer of the next [Self::box_candidates]
+    box_scratch: Vec<usize>,
+    /// how far the wheel went through [Self::box_candidates]
+    box_cycle: usize,
+    /// the scroll not yet turned into a step through the boxes
+    scroll: f64,
+    /// the label prefix typed to jump to a box
+    typed: String,
+    /// Time of the last key of [Self::typed]
+    last_typed: std::time::Instant,
     pub(crate) aspect_ratio: Option<(f64, f64)>,
+    /// the aspect ratios the wheel and Tab go through while dragging, `None` is free
+    pub(crate) aspect_presets: Vec<Option<(f64, f64)>>,
+    /// the index of [Self::aspect_ratio] in [Self::aspect_presets], `None` when it is not one of
+    /// them
+    pub(crate) aspect_preset: Option<usize>,
+    /// the point of the fixed-size selection held at the pointer
+    pub(crate) stamp_anchor: StampAnchor,
+    /// how much the wheel scaled the fixed-size selection
+    stamp_scale: f64,
+    pub(crate) size_limits: SizeLimits,
+    pub(crate) bounds: Option<Bounds>,
+    /// where the drag of the area started, the start of the area moves when it is clamped
+    drag_origin: Option<Position<f64>>,
+    /// whether the selection is clamped to the size limits or the bounds
+    clamped: bool,
     pub(crate) last_redraw: std::time::Instant,
     /// Tracks actual effective selection type for DimensionsOrOutput mode
     pub(crate) effective_selection_type: Option<SelectionType>,
     /// Time when mouse was pressed down
     pub(crate) mouse_press_time: Option<std::time::Instant>,
+    /// Where the mouse was pressed down in BoxesOrDimensions mode, until it is released
+    pub(crate) press_pos: Option<Position<f64>>,
+    /// Time of the last pointer event or key press, the marching ants stop when it is too old
+    pub(crate) last_input: std::time::Instant,
+    /// Origin of the motion of the marching ants
+    animation_start: std::time::Instant,
     redraw_all: bool,
+    /// whether the help panel is shown, toggled with `?`
+    pub(crate) help_visible: bool,
+    /// the keymap of the seat with its modifiers, to read the characters of the keys
+    pub(crate) keymap: Option<Keymap>,
+    /// whether a shift key is held, for Shift+Tab
+    pub(crate) shift: bool,
 }
 
 impl WaysipState {
     pub fn new(selection_type: SelectionType) -> Self {
         WaysipState {
             wloutput_infos: Vec::new(),
+            output_heads: Vec::new(),
             running: true,
             selection_type,
             wl_surfaces: Vec::new(),
@@ -204,11 +416,33 @@ impl WaysipState {
             qh: None,
             shm: None,
             predefined_boxes: None,
+            box_policy: BoxPolicy::default(),
+            selected_box: None,
+            box_candidates: Vec::new(),
+            box_scratch: Vec::new(),
+            box_cycle: 0,
+            scroll: 0.0,
+            typed: String::new(),
+            last_typed: std::time::Instant::now(),
             aspect_ratio: None,
+            aspect_presets: Vec::new(),
+            aspect_preset: None,
+            stamp_anchor: StampAnchor::default(),
+            stamp_scale: 1.0,
+            size_limits: SizeLimits::default(),
+            bounds: None,
+            drag_origin: None,
+            clamped: false,
             last_redraw: std::time::Instant::now() - std::time::Duration::from_secs(1),
             effective_selection_type: None,
             mouse_press_time: None,
+            press_pos: None,
+            last_input: std::time::Instant::now(),
+            animation_start: std::time::Instant::now(),
             redraw_all: false,
+            help_visible: false,
+            keymap: None,
+            shift: false,
         }
     }
 
@@ -224,10 +458,33 @@ impl WaysipState {
         matches!(self.selection_type, SelectionType::PredefinedBoxes)
     }
 
+    pub fn is_identify(&self) -> bool {
+        matches!(self.selection_type, SelectionType::Identify)
+    }
+
     pub fn is_dimensions_or_output(&self) -> bool {
         matches!(self.selection_type, SelectionType::DimensionsOrOutput)
     }
 
+    pub fn is_boxes_or_dimensions(&self) -> bool {
+        matches!(self.selection_type, SelectionType::BoxesOrDimensions)
+    }
+
+    pub fn is_fixed_size(&self) -> bool {
+        matches!(self.selection_type, SelectionType::FixedSize(..))
+    }
+
+    /// Check if the pointer drags an area, or is about to
+    pub fn is_selecting_area(&self) -> bool {
+        self.is_area() || self.is_dimensions_or_output() || self.is_effective_area()
+    }
+
+    /// Check if the pointer picks the predefined boxes, that is not while dragging an area in
+    /// BoxesOrDimensions mode
+    pub fn is_picking_boxes(&self) -> bool {
+        self.is_predefined_boxes() || (self.is_boxes_or_dimensions() && !self.is_effective_area())
+    }
+
     /// Get the effective selection type, considering DimensionsOrOutput mode
     pub fn effective_selection_type(&self) -> SelectionType {
         self.effective_selection_type.unwrap_or(self.selection_type)
@@ -243,8 +500,454 @@ impl WaysipState {
         matches!(self.effective_selection_type(), SelectionType::Screen)
     }
 
+    /// copy the data of zwlr_output_manager_v1 heads into the outputs with the same name
+    pub(crate) fn apply_output_heads(&mut self) {
+        for head in self.output_heads.iter() {
+            let Some(output) = self
+                .wloutput_infos
+                .iter_mut()
+                .find(|info| info.name == head.name)
+            else {
+                continue;
+            };
+            if head.scale.is_some() {
+                output.fractional_scale = head.scale;
+            }
+            if head.enabled.is_some() {
+                output.enabled = head.enabled;
+            }
+        }
+    }
+
+    /// Select the predefined box under the pointer picked by the policy, or the next ones after
+    /// scrolling. The selection stays when the pointer leaves the boxes.
+    pub(crate) fn update_hovered_box(&mut self) {
+        let Some(boxes) = self.predefined_boxes.as_ref() else {
+            return;
+        };
+        // NOTE: this runs on every motion, so the candidates reuse the buffers instead of
+        // allocating
+        let mut candidates = std::mem::take(&mut self.box_scratch);
+        candidates.clear();
+        candidates.extend(boxes.at(self.current_pos));
+        let area = |index: usize| {
+            let box_info = &boxes[index];
+            (box_info.end_x - box_info.start_x) * (box_info.end_y - box_info.start_y)
+        };
+        // the later boxes are drawn above, so they win the ties
+        candidates.reverse();
+        match self.box_policy {
+            BoxPolicy::Smallest => {
+                candidates.sort_unstable_by(|a, b| area(*a).total_cmp(&area(*b)).then(b.cmp(a)))
+            }
+            BoxPolicy::Topmost => {}
+            BoxPolicy::ZOrder => {
+                candidates.sort_unstable_by(|a, b| boxes[*b].z.cmp(&boxes[*a].z).then(b.cmp(a)))
+            }
+        }
+        if candidates != self.box_candidates {
+            self.box_cycle = 0;
+            std::mem::swap(&mut candidates, &mut self.box_candidates);
+        }
+        self.box_scratch = candidates;
+        if self.box_candidates.is_empty() {
+            return;
+        }
+        let index = self.box_candidates[self.box_cycle % self.box_candidates.len()];
+        let box_info = &boxes[index];
+        let (start, end) = (
+            Position {
+                x: box_info.start_x,
+                y: box_info.start_y,
+            },
+            Position {
+                x: box_info.end_x,
+                y: box_info.end_y,
+            },
+        );
+        self.selected_box = Some(index);
+        self.set_start_pos(start);
+        self.end_pos = Some(end);
+    }
+
+    /// whether a predefined box is under the pointer
+    pub(crate) fn is_over_box(&self) -> bool {
+        !self.box_candidates.is_empty()
+    }
+
+    /// In BoxesOrDimensions mode, turn the press into a free area once the pointer moved away
+    /// from where it was pressed.
+    pub(crate) fn update_drag(&mut self) {
+        let Some(press_pos) = self.press_pos else {
+            return;
+        };
+        if self.is_effective_area() {
+            return;
+        }
+        let dx = self.current_pos.x - press_pos.x;
+        let dy = self.current_pos.y - press_pos.y;
+        if (dx * dx + dy * dy).sqrt() < DRAG_THRESHOLD {
+            return;
+        }
+        self.effective_selection_type = Some(SelectionType::Area);
+        self.selected_box = None;
+        self.start_pos = Some(press_pos);
+        self.redraw_all = true;
+    }
+
+    /// Step through the predefined boxes under the pointer with the wheel, one box per notch.
+    pub(crate) fn scroll_boxes(&mut self, value: f64) {
+        self.scroll += value;
+        let steps = (self.scroll / SCROLL_STEP).trunc();
+        if steps == 0.0 || self.box_candidates.is_empty() {
+            return;
+        }
+        self.scroll -= steps * SCROLL_STEP;
+        let len = self.box_candidates.len() as i64;
+        self.box_cycle = (self.box_cycle as i64 + steps as i64).rem_euclid(len) as usize;
+        self.update_hovered_box();
+        self.commit();
+    }
+
+    /// the size of the fixed-size selection, as scaled by the wheel
+    fn stamp_size(&self) -> Option<(f64, f64)> {
+        let SelectionType::FixedSize(width, height) = self.selection_type else {
+            return None;
+        };
+        Some((
+            (width as f64 * self.stamp_scale).round().max(1.0),
+            (height as f64 * self.stamp_scale).round().max(1.0),
+        ))
+    }
+
+    /// Place the fixed-size selection at the pointer, by its anchor.
+    pub(crate) fn update_stamp(&mut self) {
+        let Some((width, height)) = self.stamp_size() else {
+            return;
+        };
+        let Position { x, y } = self.current_pos;
+        let (x, y) = match self.stamp_anchor {
+            StampAnchor::Center => (x - width / 2.0, y - height / 2.0),
+            StampAnchor::TopLeft => (x, y),
+            StampAnchor::TopRight => (x - width, y),
+            StampAnchor::BottomLeft => (x, y - height),
+            StampAnchor::BottomRight => (x - width, y - height),
+        };
+        let start = Position {
+            x: x.round(),
+            y: y.round(),
+        };
+        let end = Position {
+            x: start.x + width,
+            y: start.y + height,
+        };
+        let bounds = self.bounds_rect(self.current_pos);
+        let (fitted_start, fitted_end) =
+            constrain(start, end, Some(width / height), &self.size_limits, bounds);
+        self.clamped = moved(start, fitted_start) || moved(end, fitted_end);
+        self.set_start_pos(fitted_start);
+        self.end_pos = Some(fitted_end);
+    }
+
+    /// Go `step` aspect presets forward, or backward when negative, and refit the area.
+    pub(crate) fn step_aspect(&mut self, step: i64) {
+        if self.aspect_presets.is_empty() || step == 0 {
+            return;
+        }
+        let len = self.aspect_presets.len() as i64;
+        let index = match self.aspect_preset {
+            Some(index) => (index as i64 + step).rem_euclid(len),
+            // from a ratio out of the presets, the first step lands on the first or last one
+            None if step > 0 => (step - 1).rem_euclid(len),
+            None => (len + step).rem_euclid(len),
+        } as usize;
+        self.aspect_preset = Some(index);
+        self.aspect_ratio = self.aspect_presets[index];
+        self.update_area();
+        self.commit();
+    }
+
+    /// Go through the aspect presets with the wheel, one preset per notch.
+    pub(crate) fn scroll_aspect(&mut self, value: f64) {
+        self.scroll += value;
+        let steps = (self.scroll / SCROLL_STEP).trunc();
+        if steps == 0.0 {
+            return;
+        }
+        self.scroll -= steps * SCROLL_STEP;
+        self.step_aspect(steps as i64);
+    }
+
+    /// Set the end of the area dragged to the pointer, keeping the aspect ratio, then fit the
+    /// area into the size limits and the bounds.
+    pub(crate) fn update_area(&mut self) {
+        // NOTE: before the drag starts, the area is only the pointer
+        let Some(start_pos) = self.start_pos else {
+            self.end_pos = Some(self.current_pos);
+            return;
+        };
+        let origin = *self.drag_origin.get_or_insert(start_pos);
+        let end_pos = match self.aspect_ratio {
+            Some((width_rel, height_rel)) => {
+                let width = self.current_pos.x - origin.x;
+                let height = self.current_pos.y - origin.y;
+                if width_rel / height_rel > width / height {
+                    Position {
+                        x: origin.x + height * width_rel / height_rel,
+                        y: origin.y + height,
+                    }
+                } else {
+                    Position {
+                        x: origin.x + width,
+                        y: origin.y + width * height_rel / width_rel,
+                    }
+                }
+            }
+            None => self.current_pos,
+        };
+        let bounds = self.bounds_rect(origin);
+        let (start, end) = constrain(
+            clamp_point(origin, bounds),
+            clamp_point(end_pos, bounds),
+            self.aspect_ratio.map(|(width, height)| width / height),
+            &self.size_limits,
+            bounds,
+        );
+        self.clamped = moved(origin, start) || moved(end_pos, end);
+        self.start_pos = Some(start);
+        self.end_pos = Some(end);
+    }
+
+    /// The bounds as `[x1, y1, x2, y2]`, [Bounds::Output] is the output under `origin`. `None`
+    /// without bounds, or when their output or box does not exist.
+    pub(crate) fn bounds_rect(&self, origin: Position<f64>) -> Option<[f64; 4]> {
+        let output_rect = |output: &WlOutputInfo| {
+            let ZXdgOutputInfo {
+                size,
+                start_position,
+                ..
+            } = output.xdg_output_info();
+            [
+                start_position.x as f64,
+                start_position.y as f64,
+                (start_position.x + size.width) as f64,
+                (start_position.y + size.height) as f64,
+            ]
+        };
+        match self.bounds.as_ref()? {
+            Bounds::Output => self
+                .wloutput_infos
+                .iter()
+                .map(output_rect)
+                .find(|[x1, y1, x2, y2]| {
+                    origin.x >= *x1 && origin.x < *x2 && origin.y >= *y1 && origin.y < *y2
+                })
+                .or_else(|| {
+                    self.wloutput_infos
+                        .get(self.current_screen)
+                        .map(output_rect)
+                }),
+            Bounds::NamedOutput(name) => self
+                .wloutput_infos
+                .iter()
+                .find(|output| output.name == *name || output.xdg_output_info().name == *name)
+                .map(output_rect),
+            Bounds::Box(label) => self
+                .predefined_boxes
+                .as_ref()?
+                .iter()
+                .find(|box_info| box_info.label.as_deref() == Some(label.as_str()))
+                .map(|box_info| {
+                    [
+                        box_info.start_x.min(box_info.end_x),
+                        box_info.start_y.min(box_info.end_y),
+                        box_info.start_x.max(box_info.end_x),
+                        box_info.start_y.max(box_info.end_y),
+                    ]
+                }),
+            Bounds::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => Some([
+                x.min(x + width),
+                y.min(y + height),
+                x.max(x + width),
+                y.max(y + height),
+            ]),
+        }
+    }
+
+    /// Scale the fixed-size selection with the wheel, a step per notch, down when scrolling
+    /// down. It does not get smaller than a pixel on its short side.
+    pub(crate) fn scroll_stamp(&mut self, value: f64) {
+        self.scroll += value;
+        let steps = (self.scroll / SCROLL_STEP).trunc();
+        let Some((width, height)) = self.stamp_size() else {
+            return;
+        };
+        if steps == 0.0 {
+            return;
+        }
+        self.scroll -= steps * SCROLL_STEP;
+        let smallest = self.stamp_scale / width.min(height);
+        self.stamp_scale = (self.stamp_scale * STAMP_SCALE_STEP.powf(-steps)).max(smallest);
+        self.update_stamp();
+        self.commit();
+    }
+
+    /// Select the predefined box with this index from the keyboard, on the output under its
+    /// center.
+    fn select_box(&mut self, index: usize) {
+        let Some(box_info) = self
+            .predefined_boxes
+            .as_ref()
+            .and_then(|boxes| boxes.get(index))
+        else {
+            return;
+        };
+        let (start, end) = (
+            Position {
+                x: box_info.start_x,
+                y: box_info.start_y,
+            },
+            Position {
+                x: box_info.end_x,
+                y: box_info.end_y,
+            },
+        );
+        let center = box_info.center();
+        if let Some(screen) = self.wloutput_infos.iter().position(|output| {
+            let ZXdgOutputInfo {
+                size,
+                start_position,
+                ..
+            } = output.xdg_output_info();
+            center.x >= start_position.x as f64
+                && center.x < (start_position.x + size.width) as f64
+                && center.y >= start_position.y as f64
+                && center.y < (start_position.y + size.height) as f64
+        }) {
+            self.current_screen = screen;
+        }
+        self.selected_box = Some(index);
+        self.set_start_pos(start);
+        self.end_pos = Some(end);
+        self.commit();
+    }
+
+    /// Select the nearest predefined box in this direction from the selected box, or from the
+    /// pointer.
+    pub(crate) fn move_box(&mut self, direction: Direction) {
+        let Some(boxes) = self.predefined_boxes.as_ref() else {
+            return;
+        };
+        let from = match self.selected_box.and_then(|index| boxes.get(index)) {
+            Some(box_info) => box_info.center(),
+            None => self.current_pos,
+        };
+        let nearest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != self.selected_box)
+            .filter_map(|(index, box_info)| {
+                let to = box_info.center();
+                let (along, across) = match direction {
+                    Direction::Left => (from.x - to.x, to.y - from.y),
+                    Direction::Right => (to.x - from.x, to.y - from.y),
+                    Direction::Up => (from.y - to.y, to.x - from.x),
+                    Direction::Down => (to.y - from.y, to.x - from.x),
+                };
+                // the boxes straight ahead come before the closer ones off to the side
+                (along > 0.0).then_some((index, along + 2.0 * across.abs()))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index);
+        if let Some(index) = nearest {
+            self.select_box(index);
+        }
+    }
+
+    /// Select the next predefined box in input order, or the previous one with a negative step.
+    pub(crate) fn step_box(&mut self, step: i64) {
+        let Some(len) = self.predefined_boxes.as_ref().map(|boxes| boxes.len()) else {
+            return;
+        };
+        if len == 0 {
+            return;
+        }
+        let index = match self.selected_box {
+            Some(index) => (index as i64 + step).rem_euclid(len as i64) as usize,
+            None if step < 0 => len - 1,
+            None => 0,
+        };
+        self.select_box(index);
+    }
+
+    /// Add a character to the typed label prefix and select the first predefined box whose
+    /// label starts with it, ignoring case.
+    pub(crate) fn type_box_label(&mut self, c: char) {
+        if self.last_typed.elapsed() > TYPE_TIMEOUT {
+            self.typed.clear();
+        }
+        self.last_typed = std::time::Instant::now();
+        self.typed.extend(c.to_lowercase());
+        let Some(boxes) = self.predefined_boxes.as_ref() else {
+            return;
+        };
+        let found = boxes.iter().position(|box_info| {
+            box_info
+                .label
+                .as_ref()
+                .is_some_and(|label| label.to_lowercase().starts_with(&self.typed))
+        });
+        if let Some(index) = found {
+            self.select_box(index);
+        }
+    }
+
+    /// Confirm the selected predefined box from the keyboard.
+    pub(crate) fn confirm_box(&mut self) {
+        if self.selected_box.is_none() {
+            return;
+        }
+        if self.is_boxes_or_dimensions() {
+            self.effective_selection_type = Some(SelectionType::PredefinedBoxes);
+        }
+        self.running = false;
+    }
+
+    /// Replace the predefined boxes and redraw them. The selected box stays selected if a box with
+    /// its label is still there, else the box under the pointer is picked again.
     pub fn set_boxes(&mut self, boxes: Vec<BoxInfo>) {
-        self.predefined_boxes = Some(boxes);
+        let label = self
+            .selected_box
+            .and_then(|index| self.predefined_boxes.as_ref()?.get(index)?.label.clone());
+        self.predefined_boxes = Some(BoxSet::new(boxes));
+        self.box_candidates.clear();
+        self.box_cycle = 0;
+        self.redraw_all = true;
+        if !self.is_picking_boxes() {
+            self.commit();
+            return;
+        }
+        self.selected_box = None;
+        self.start_pos = None;
+        self.end_pos = None;
+        let kept = label.and_then(|label| {
+            self.predefined_boxes
+                .as_ref()?
+                .iter()
+                .position(|box_info| box_info.label.as_ref() == Some(&label))
+        });
+        match kept {
+            Some(index) => self.select_box(index),
+            None => {
+                self.update_hovered_box();
+                self.commit();
+            }
+        }
     }
 
     pub(crate) fn ensure_buffer(
@@ -272,7 +975,7 @@ impl WaysipState {
         } = render::draw_ui(
             &mut file,
             (width, height),
-            surface_info.style.background_color,
+            surface_info.canvas.style.background_color,
         );
         let pool = self
             .shm
@@ -283,7 +986,7 @@ impl WaysipState {
         let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, qh, ());
 
         let old_buffer = std::mem::replace(&mut surface_info.buffer, buffer);
-        let old_cairo_t = std::mem::replace(&mut surface_info.cairo_t, cairo_t);
+        let old_cairo_t = std::mem::replace(&mut surface_info.canvas.cairo_t, cairo_t);
         old_buffer.destroy();
         drop(old_cairo_t);
 
@@ -292,18 +995,68 @@ impl WaysipState {
     }
 
     pub fn ensure_init(&mut self, surface: &ZwlrLayerSurfaceV1) {
-        let Some(surface_info) = self
+        let Some(index) = self
             .wl_surfaces
-            .iter_mut()
-            .find(|info| info.layer == *surface)
+            .iter()
+            .position(|info| info.layer == *surface)
         else {
             return;
         };
+        let surface_info = &mut self.wl_surfaces[index];
         if surface_info.inited {
             return;
         }
         surface_info.init_commit();
         surface_info.inited = true;
+        // nothing happens in identify mode, the cards are drawn right away
+        if self.is_identify() {
+            self.redraw_surface(index);
+        }
+    }
+
+    /// whether a surface draws guides that follow the pointer
+    pub(crate) fn has_guides(&self) -> bool {
+        self.wl_surfaces
+            .iter()
+            .any(|info| info.canvas.style.crosshair || info.canvas.style.rulers)
+    }
+
+    /// whether the marching ants are moving, so that the next frame should be drawn
+    pub(crate) fn is_animating(&self) -> bool {
+        self.running
+            && self.start_pos.is_some()
+            && self.last_input.elapsed() < ANTS_IDLE_TIMEOUT
+            && self
+                .wl_surfaces
+                .iter()
+                .any(|info| info.canvas.style.marching_ants)
+    }
+
+    /// show or hide the help panel
+    pub(crate) fn toggle_help(&mut self) {
+        self.help_visible = !self.help_visible;
+        self.commit();
+    }
+
+    /// the status line and the help panel of the surface with this index, only on the focused
+    /// output
+    fn hud(&self, index: usize) -> Hud {
+        if index != self.current_screen {
+            return Hud::default();
+        }
+        let status_line = self.wl_surfaces[index].canvas.style.status_line;
+        Hud {
+            status: status_line.then(|| status_text(self.selection_type, self.aspect_ratio)),
+            help: self.help_visible.then(|| {
+                help_text(
+                    self.selection_type,
+                    self.aspect_ratio,
+                    !self.aspect_presets.is_empty(),
+                    &self.size_limits,
+                    self.bounds.as_ref(),
+                )
+            }),
+        }
     }
 
     pub(crate) fn set_start_pos(&mut self, start_pos: Position<f64>) {
@@ -314,7 +1067,10 @@ impl WaysipState {
     }
 
     pub fn commit(&self) {
-        let qh = self.qh.as_ref().unwrap();
+        // nothing is shown yet
+        let Some(qh) = self.qh.as_ref() else {
+            return;
+        };
         for (idx, surface) in self.wl_surfaces.iter().enumerate() {
             surface.wl_surface.frame(qh, idx);
             surface.wl_surface.commit();
@@ -335,11 +1091,15 @@ impl WaysipState {
             return;
         }
 
+        if self.is_identify() {
+            let info = self.wloutput_infos[screen_index].get_screen_info();
+            self.wl_surfaces[screen_index].redraw_identify(screen_index, &info);
+            return;
+        }
+
         let ZXdgOutputInfo {
             size,
             start_position,
-            name,
-            description,
             ..
         } = self.wloutput_infos[screen_index].xdg_output_info().clone();
 
@@ -350,10 +1110,20 @@ impl WaysipState {
             || (self.is_dimensions_or_output() && self.start_pos.is_none())
         {
             let current_screen = self.current_screen;
+            let card = self.wl_surfaces[current_screen]
+                .canvas
+                .style
+                .screen_card
+                .as_ref()
+                .map(|template| {
+                    let info = self.screen_area_info(current_screen);
+                    template.render(&AreaFields::new(&info, true))
+                });
             for idx in 0..self.wl_surfaces.len() {
                 if !self.wl_surfaces[idx].inited {
                     continue;
                 }
+                self.wl_surfaces[idx].canvas.hud = self.hud(idx);
                 let ZXdgOutputInfo {
                     size,
                     start_position,
@@ -363,26 +1133,48 @@ impl WaysipState {
                     idx == current_screen,
                     size,
                     start_position,
-                    &name,
-                    &description,
+                    card.as_deref(),
+                    self.current_pos,
                 );
             }
         } else {
+            self.wl_surfaces[screen_index].canvas.hud = self.hud(screen_index);
+            self.wl_surfaces[screen_index].canvas.hovered_box = self.selected_box.is_some();
+            self.wl_surfaces[screen_index].canvas.clamped = self.clamped;
             if self.start_pos.is_none() {
+                let current_pos = self.current_pos;
+                self.wl_surfaces[screen_index].redraw_pointer(current_pos, start_position, size);
                 return;
             }
             let start_pos = self.start_pos.unwrap();
             let end_pos = self.end_pos.unwrap_or(start_pos);
-            let draw_text =
-                self.is_area() || self.is_effective_area() || self.is_dimensions_or_output();
+            let draw_text = self.is_area()
+                || self.is_effective_area()
+                || self.is_dimensions_or_output()
+                || self.is_fixed_size();
+            let label = match &self.wl_surfaces[screen_index].canvas.style.label {
+                Some(template) if draw_text => {
+                    let info = self.area_info_between(start_pos, end_pos);
+                    let mut text = template.render(&AreaFields::new(&info, false));
+                    // the preset the wheel and Tab went to
+                    if !self.aspect_presets.is_empty() && !self.is_fixed_size() {
+                        text.push_str("  ");
+                        text.push_str(&aspect_name(self.aspect_ratio));
+                    }
+                    Some(text)
+                }
+                _ => None,
+            };
 
             self.wl_surfaces[screen_index].redraw(
                 start_pos,
                 end_pos,
                 start_position,
                 size,
-                draw_text,
+                self.current_pos,
+                label.as_deref(),
                 self.predefined_boxes.as_ref(),
+                self.animation_start.elapsed().as_secs_f64() * ANTS_SPEED,
                 self.redraw_all,
             );
         }
@@ -393,23 +1185,262 @@ impl WaysipState {
         if self.start_pos.is_none() || self.end_pos.is_none() {
             return None;
         }
+        Some(self.area_info_between(self.start_pos.unwrap(), self.end_pos.unwrap()))
+    }
+
+    /// the [AreaInfo] of the whole screen with this index
+    fn screen_area_info(&self, index: usize) -> AreaInfo {
+        let output = &self.wloutput_infos[index];
+        let ZXdgOutputInfo {
+            size,
+            start_position,
+            ..
+        } = output.xdg_output_info();
+        AreaInfo {
+            box_info: BoxInfo {
+                start_x: start_position.x as f64,
+                start_y: start_position.y as f64,
+                end_x: (start_position.x + size.width) as f64,
+                end_y: (start_position.y + size.height) as f64,
+                label: None,
+                color: None,
+                z: 0,
+            },
+            screen_info: output.get_screen_info(),
+            effective_selection_type: self.effective_selection_type,
+        }
+    }
+
+    /// the [AreaInfo] of the area between two points, on the current screen
+    fn area_info_between(&self, start_pos: Position<f64>, end_pos: Position<f64>) -> AreaInfo {
         let Position {
             x: start_x,
             y: start_y,
-        } = self.start_pos.unwrap();
-        let Position { x: end_x, y: end_y } = self.end_pos.unwrap();
-        let output = self.wloutput_infos[self.current_screen].clone();
-        Some(AreaInfo {
+        } = start_pos;
+        let Position { x: end_x, y: end_y } = end_pos;
+        let output = &self.wloutput_infos[self.current_screen];
+        let selected_box = self
+            .selected_box
+            .and_then(|index| self.predefined_boxes.as_ref()?.get(index));
+        AreaInfo {
             box_info: BoxInfo {
                 start_x,
                 start_y,
                 end_x,
                 end_y,
+                label: selected_box.and_then(|box_info| box_info.label.clone()),
+                color: selected_box.and_then(|box_info| box_info.color),
+                z: selected_box.map_or(0, |box_info| box_info.z),
             },
             screen_info: output.get_screen_info(),
             effective_selection_type: self.effective_selection_type,
-        })
+        }
+    }
+}
+
+/// Fit the area from `origin` to `target` into `limits` and `bounds`, as its new start and end,
+/// keeping the width:height `ratio`. The area grows away from `origin`, and moves back into the
+/// bounds when the minimum size makes it leave them. The bounds win over the minimum size.
+fn constrain(
+    origin: Position<f64>,
+    target: Position<f64>,
+    ratio: Option<f64>,
+    limits: &SizeLimits,
+    bounds: Option<[f64; 4]>,
+) -> (Position<f64>, Position<f64>) {
+    let [left, top, right, bottom] = bounds.unwrap_or([
+        f64::NEG_INFINITY,
+        f64::NEG_INFINITY,
+        f64::INFINITY,
+        f64::INFINITY,
+    ]);
+    let max_width = limits.max_width.min(right - left);
+    let max_height = limits.max_height.min(bottom - top);
+    let dx = target.x - origin.x;
+    let dy = target.y - origin.y;
+    let (width, height) = match ratio {
+        Some(ratio) => {
+            let width = dx
+                .abs()
+                .min(dy.abs() * ratio)
+                .max(limits.min_width.max(limits.min_height * ratio))
+                .min(max_width.min(max_height * ratio));
+            (width, width / ratio)
+        }
+        None => (
+            dx.abs().max(limits.min_width).min(max_width),
+            dy.abs().max(limits.min_height).min(max_height),
+        ),
+    };
+    let direction = |delta: f64| if delta < 0.0 { -1.0 } else { 1.0 };
+    let (x1, x2) = fit_span(origin.x, origin.x + direction(dx) * width, left, right);
+    let (y1, y2) = fit_span(origin.y, origin.y + direction(dy) * height, top, bottom);
+    (Position { x: x1, y: y1 }, Position { x: x2, y: y2 })
+}
+
+/// the span from `start` to `end` moved into `[low, high]`, it fits in it
+fn fit_span(start: f64, end: f64, low: f64, high: f64) -> (f64, f64) {
+    let shift = (low - start.min(end)).max(0.0) + (high - start.max(end)).min(0.0);
+    (start + shift, end + shift)
+}
+
+/// the point moved into `bounds`
+fn clamp_point(point: Position<f64>, bounds: Option<[f64; 4]>) -> Position<f64> {
+    match bounds {
+        Some([x1, y1, x2, y2]) => Position {
+            x: point.x.clamp(x1, x2),
+            y: point.y.clamp(y1, y2),
+        },
+        None => point,
+    }
+}
+
+/// whether a corner of the area moved by more than rounding
+fn moved(before: Position<f64>, after: Position<f64>) -> bool {
+    (before.x - after.x).abs() > 0.5 || (before.y - after.y).abs() > 0.5
+}
+
+/// What the mouse does in each mode, as `(input, action)`
+fn mode_bindings(
+    selection_type: SelectionType,
+) -> (&'static str, &'static [(&'static str, &'static str)]) {
+    match selection_type {
+        SelectionType::Area => ("area", &[("drag", "select an area")]),
+        SelectionType::Point => ("point", &[("click", "select a point")]),
+        SelectionType::Screen => ("output", &[("click", "select the output")]),
+        SelectionType::PredefinedBoxes => ("boxes", &[("click", "select the box")]),
+        SelectionType::DimensionsOrOutput => (
+            "area or output",
+            &[("drag", "select an area"), ("click", "select the output")],
+        ),
+        SelectionType::BoxesOrDimensions => (
+            "boxes or area",
+            &[("drag", "select an area"), ("click", "select the box")],
+        ),
+        SelectionType::FixedSize(..) => (
+            "fixed size",
+            &[("click", "place the area"), ("wheel", "scale the area")],
+        ),
+        SelectionType::Identify => ("identify", &[("any key", "close")]),
+    }
+}
+
+/// The status line: the mode, the aspect ratio and the main keys.
+pub(crate) fn status_text(
+    selection_type: SelectionType,
+    aspect_ratio: Option<(f64, f64)>,
+) -> String {
+    let (mode, bindings) = mode_bindings(selection_type);
+    let mut parts = vec![mode.to_string()];
+    parts.extend(
+        bindings
+            .iter()
+            .map(|(input, action)| format!("{input}: {action}")),
+    );
+    if let Some((width, height)) = aspect_ratio {
+        parts.push(format!("aspect {width}:{height}"));
+    }
+    parts.push("Esc: cancel".to_string());
+    parts.push("?: help".to_string());
+    parts.join("  ·  ")
+}
+
+/// an aspect ratio as `16:9`, or `free`
+fn aspect_name(aspect_ratio: Option<(f64, f64)>) -> String {
+    match aspect_ratio {
+        Some((width, height)) => format!("{width}:{height}"),
+        None => "free".to_string(),
+    }
+}
+
+/// The keys going through the aspect presets, as `(input, action)`
+const ASPECT_KEYS: &[(&str, &str)] = &[
+    ("wheel, Tab", "next aspect ratio"),
+    ("Shift+Tab", "previous aspect ratio"),
+];
+
+/// The keys moving through the predefined boxes, as `(input, action)`
+const BOX_KEYS: &[(&str, &str)] = &[
+    ("wheel", "next box under the pointer"),
+    ("arrows", "nearest box that way"),
+    ("Tab", "next box, Shift+Tab previous"),
+    ("a-z 0-9", "box whose label starts so"),
+    ("Enter", "select the box"),
+];
+
+/// The help panel: every binding of the mode and its constraints.
+pub(crate) fn help_text(
+    selection_type: SelectionType,
+    aspect_ratio: Option<(f64, f64)>,
+    aspect_presets: bool,
+    limits: &SizeLimits,
+    bounds: Option<&Bounds>,
+) -> String {
+    let (mode, bindings) = mode_bindings(selection_type);
+    let mut lines = vec![format!("waysip: select {mode}"), String::new()];
+    lines.extend(
+        bindings
+            .iter()
+            .map(|(input, action)| format!("{input}\t{action}")),
+    );
+    if matches!(
+        selection_type,
+        SelectionType::PredefinedBoxes | SelectionType::BoxesOrDimensions
+    ) {
+        lines.extend(
+            BOX_KEYS
+                .iter()
+                .map(|(input, action)| format!("{input}\t{action}")),
+        );
+    }
+    if aspect_presets
+        && matches!(
+            selection_type,
+            SelectionType::Area
+                | SelectionType::DimensionsOrOutput
+                | SelectionType::BoxesOrDimensions
+        )
+    {
+        lines.extend(
+            ASPECT_KEYS
+                .iter()
+                .map(|(input, action)| format!("{input}\t{action}")),
+        );
     }
+    lines.push("Esc\tcancel".to_string());
+    lines.push("?, F1\tshow or hide this help".to_string());
+    let mut constraints = Vec::new();
+    if let Some((width, height)) = aspect_ratio {
+        constraints.push(format!("the area keeps the aspect ratio {width}:{height}"));
+    }
+    for (side, bound, value) in [
+        ("width", "at least", limits.min_width),
+        ("height", "at least", limits.min_height),
+        ("width", "at most", limits.max_width),
+        ("height", "at most", limits.max_height),
+    ] {
+        if value > 0.0 && value.is_finite() {
+            constraints.push(format!("the {side} is {bound} {value}"));
+        }
+    }
+    if let Some(bounds) = bounds {
+        constraints.push(match bounds {
+            Bounds::Output => "the area stays on the output it starts on".to_string(),
+            Bounds::NamedOutput(name) => format!("the area stays on {name}"),
+            Bounds::Box(label) => format!("the area stays inside the box {label}"),
+            Bounds::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => format!("the area stays inside {x},{y} {width}x{height}"),
+        });
+    }
+    if !constraints.is_empty() {
+        lines.push(String::new());
+        lines.extend(constraints);
+    }
+    lines.join("\n")
 }
 
 #[derive(Debug)]
@@ -419,33 +1450,72 @@ pub(crate) struct LayerSurfaceInfo {
     pub cursor_surface: WlSurface,
     pub buffer: WlBuffer,
     pub cursor_buffer: Option<CursorImageBuffer>,
-    pub cairo_t: cairo::Context,
     #[allow(unused)]
     pub stride: i32,
     pub inited: bool,
     pub buffer_busy: bool,
-    pub style: Style,
-    pub pango_layout: std::cell::OnceCell<pango::Layout>,
-    pub font_desc_bold: std::cell::OnceCell<pango::FontDescription>,
-    pub font_desc_normal: std::cell::OnceCell<pango::FontDescription>,
-    pub prev_selection: Option<[f64; 4]>,
-    pub margin: std::cell::OnceCell<(f64, f64)>,
+    pub canvas: Canvas,
 }
 
 /// coordinates of box
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct BoxInfo {
     pub start_x: f64,
     pub start_y: f64,
     pub end_x: f64,
     pub end_y: f64,
+    /// the label of a predefined box, drawn on it and returned with the selection
+    pub label: Option<String>,
+    /// the fill of a predefined box, `None` to use the box color of the style
+    pub color: Option<Color>,
+    /// the stacking order of a predefined box for [BoxPolicy::ZOrder], higher is above
+    pub z: i32,
 }
 
 impl BoxInfo {
+    /// a box from `start` to `end`, without label or color
+    pub fn new(start_x: f64, start_y: f64, end_x: f64, end_y: f64) -> Self {
+        Self {
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            label: None,
+            color: None,
+            z: 0,
+        }
+    }
+
+    /// the center of the box
+    pub(crate) fn center(&self) -> Position<f64> {
+        Position {
+            x: (self.start_x + self.end_x) / 2.0,
+            y: (self.start_y + self.end_y) / 2.0,
+        }
+    }
+
+    /// Parse a box in the form `x,y WIDTHxHEIGHT [#rrggbbaa] [label]`, the label being the rest of
+    /// the line. A first word of exactly `#` and eight hex digits is always the color, so a label
+    /// starting with such a word needs a color before it.
     pub fn get_box_from_str(box_string: &str) -> Result<Self, BoxInfoError> {
-        let (coords, size) = box_string
-            .split_once(' ')
+        let (coords, rest) = box_string
+            .trim()
+            .split_once(char::is_whitespace)
             .ok_or(BoxInfoError::InvalidBoxString(box_string.to_string()))?;
+        let rest = rest.trim_start();
+        let (size, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let mut label = rest.trim();
+        let mut color = None;
+        let (word, text) = label.split_once(char::is_whitespace).unwrap_or((label, ""));
+        let is_color = word.len() == 9 && word.starts_with('#') && !word[1..].starts_with('#');
+        if let Some(parsed) = is_color
+            .then(|| Color::hex_to_color(word.to_string()).ok())
+            .flatten()
+        {
+            color = Some(parsed);
+            label = text.trim();
+        }
         let (start_x, start_y) = coords
             .split_once(',')
             .ok_or(BoxInfoError::InvalidBoxCoordsString(coords.to_string()))?;
@@ -464,11 +1534,21 @@ impl BoxInfo {
         let height = height
             .parse::<f64>()
             .map_err(BoxInfoError::ParseFloatError)?;
+        // `nan` and `inf` parse as floats
+        if !(start_x.is_finite() && start_y.is_finite()) {
+            return Err(BoxInfoError::InvalidBoxCoordsString(coords.to_string()));
+        }
+        if !(width.is_finite() && height.is_finite()) {
+            return Err(BoxInfoError::InvalidBoxSizeString(size.to_string()));
+        }
         Ok(BoxInfo {
             start_x,
             start_y,
             end_x: start_x + width,
             end_y: start_y + height,
+            label: (!label.is_empty()).then(|| label.to_string()),
+            color,
+            z: 0,
         })
     }
 }
@@ -528,4 +1608,496 @@ impl AreaInfo {
     pub fn selected_screen_info(&self) -> &ScreenInfo {
         &self.screen_info
     }
+
+    /// the ratio between the physical pixels and the logical coordinates of the chosen screen,
+    /// for the x and the y axis of the screen as it is displayed
+    pub fn physical_scale(&self) -> (f64, f64) {
+        let Size { width, height } = self.screen_info.get_size();
+        let Size {
+            width: mode_w,
+            height: mode_h,
+        } = transformed_size(
+            self.screen_info.get_wloutput_size(),
+            self.screen_info.get_transform(),
+        );
+        if width <= 0 || height <= 0 || mode_w <= 0 || mode_h <= 0 {
+            let scale = self.screen_info.get_scale();
+            return (scale, scale);
+        }
+        (mode_w as f64 / width as f64, mode_h as f64 / height as f64)
+    }
+
+    /// the area in the buffer pixels of the chosen screen, so the transform of the screen is
+    /// undone and the area is clipped to the screen. Edges are rounded separately, so areas
+    /// sharing an edge stay adjacent at fractional scales.
+    pub fn physical_left_top_point(&self) -> Position {
+        let [x, y, _, _] = self.physical_rect();
+        Position { x, y }
+    }
+
+    /// the size of the area in the buffer pixels of the chosen screen, see
+    /// [AreaInfo::physical_left_top_point]
+    pub fn physical_size(&self) -> Size {
+        let [_, _, width, height] = self.physical_rect();
+        Size { width, height }
+    }
+
+    fn physical_rect(&self) -> [i32; 4] {
+        let Position { x: sx, y: sy } = self.screen_info.get_position();
+        let (scale_x, scale_y) = self.physical_scale();
+        let display_size = transformed_size(
+            self.screen_info.get_wloutput_size(),
+            self.screen_info.get_transform(),
+        );
+
+        let x1 = self.box_info.start_x.min(self.box_info.end_x) - sx as f64;
+        let y1 = self.box_info.start_y.min(self.box_info.end_y) - sy as f64;
+        let x2 = self.box_info.start_x.max(self.box_info.end_x) - sx as f64;
+        let y2 = self.box_info.start_y.max(self.box_info.end_y) - sy as f64;
+
+        let px1 = ((x1 * scale_x).round() as i32).clamp(0, display_size.width);
+        let py1 = ((y1 * scale_y).round() as i32).clamp(0, display_size.height);
+        let px2 = ((x2 * scale_x).round() as i32).clamp(0, display_size.width);
+        let py2 = ((y2 * scale_y).round() as i32).clamp(0, display_size.height);
+
+        transform_rect(
+            [px1, py1, px2 - px1, py2 - py1],
+            invert_transform(self.screen_info.get_transform()),
+            display_size,
+        )
+    }
+}
+
+/// the size of the buffer once the transform is applied
+fn transformed_size(Size { width, height }: Size, transform: Transform) -> Size {
+    match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => Size {
+            width: height,
+            height: width,
+        },
+        _ => Size { width, height },
+    }
+}
+
+fn invert_transform(transform: Transform) -> Transform {
+    match transform {
+        Transform::_90 => Transform::_270,
+        Transform::_270 => Transform::_90,
+        other => other,
+    }
+}
+
+/// transform the rect `[x, y, width, height]` living in a space of the given size, the same way
+/// as wlr_box_transform
+fn transform_rect(
+    [x, y, w, h]: [i32; 4],
+    transform: Transform,
+    Size { width, height }: Size,
+) -> [i32; 4] {
+    match transform {
+        Transform::_90 => [height - y - h, x, h, w],
+        Transform::_180 => [width - x - w, height - y - h, w, h],
+        Transform::_270 => [y, width - x - w, h, w],
+        Transform::Flipped => [width - x - w, y, w, h],
+        Transform::Flipped90 => [y, x, h, w],
+        Transform::Flipped180 => [x, height - y - h, w, h],
+        Transform::Flipped270 => [height - y - h, width - x - w, h, w],
+        _ => [x, y, w, h],
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use wayland_client::{Connection, Proxy};
+
+    use super::*;
+
+    const TRANSFORMS: [Transform; 8] = [
+        Transform::Normal,
+        Transform::_90,
+        Transform::_180,
+        Transform::_270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    /// an output at `position` showing a mode of `mode` pixels as `logical` with `transform`
+    pub(crate) fn screen(
+        position: Position,
+        mode: Size,
+        logical: Size,
+        transform: Transform,
+    ) -> ScreenInfo {
+        let (client, _server) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(client).unwrap();
+        ScreenInfo {
+            position,
+            screen_size: logical,
+            wl_output: WlOutput::inert(conn.backend().downgrade()),
+            output_size: mode,
+            name: "DP-1".to_string(),
+            description: String::new(),
+            make: String::new(),
+            model: String::new(),
+            physical_size: Size {
+                width: 0,
+                height: 0,
+            },
+            subpixel: Subpixel::Unknown,
+            transform,
+            refresh: 60000,
+            preferred_mode: true,
+            scale: 1,
+            fractional_scale: None,
+            enabled: None,
+        }
+    }
+
+    pub(crate) fn area(screen_info: ScreenInfo, [x1, y1, x2, y2]: [f64; 4]) -> AreaInfo {
+        AreaInfo {
+            box_info: BoxInfo {
+                start_x: x1,
+                start_y: y1,
+                end_x: x2,
+                end_y: y2,
+                label: None,
+                color: None,
+                z: 0,
+            },
+            screen_info,
+            effective_selection_type: None,
+        }
+    }
+
+    pub(crate) fn size(width: i32, height: i32) -> Size {
+        Size { width, height }
+    }
+
+    #[test]
+    fn physical_rect_undoes_every_transform() {
+        let mode = size(200, 100);
+        // the rect at 10,20 of 30x40 on the output as it is displayed, in the buffer, the same
+        // way as wlr_region_transform with the inverted transform
+        let expected = [
+            [10, 20, 30, 40],
+            [20, 60, 40, 30],
+            [160, 40, 30, 40],
+            [140, 10, 40, 30],
+            [160, 20, 30, 40],
+            [20, 10, 40, 30],
+            [10, 40, 30, 40],
+            [140, 60, 40, 30],
+        ];
+        for (transform, expected) in TRANSFORMS.into_iter().zip(expected) {
+            let logical = transformed_size(mode, transform);
+            let screen_info = || screen(Position { x: 1000, y: 500 }, mode, logical, transform);
+            let info = area(screen_info(), [1040.0, 560.0, 1010.0, 520.0]);
+            assert_eq!(info.physical_rect(), expected, "{transform:?}");
+            // transforming the buffer rect back gives the rect as displayed
+            assert_eq!(
+                transform_rect(expected, transform, mode),
+                [10, 20, 30, 40],
+                "{transform:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn fractional_scales_keep_shared_edges() {
+        for (scale, mode) in [(1.25, size(2400, 1350)), (1.5, size(2880, 1620))] {
+            let screen_info = || {
+                screen(
+                    Position { x: 0, y: 0 },
+                    mode,
+                    size(1920, 1080),
+                    Transform::Normal,
+                )
+            };
+            assert_eq!(
+                area(screen_info(), [0.0; 4]).physical_scale(),
+                (scale, scale)
+            );
+            for step in 1..200 {
+                let edge = step as f64 * 0.3;
+                let left = area(screen_info(), [0.0, 0.0, edge, edge]).physical_rect();
+                let right = area(screen_info(), [edge, edge, 100.0, 100.0]).physical_rect();
+                assert_eq!(left[0] + left[2], right[0], "x at {edge} scale {scale}");
+                assert_eq!(left[1] + left[3], right[1], "y at {edge} scale {scale}");
+                assert_eq!(left[2] + right[2], (100.0 * scale) as i32);
+            }
+        }
+    }
+
+    #[test]
+    fn physical_rect_is_clipped_to_the_output() {
+        let mode = size(2400, 1350);
+        for transform in TRANSFORMS {
+            let logical = transformed_size(size(1920, 1080), transform);
+            let screen_info = || screen(Position { x: 1920, y: 0 }, mode, logical, transform);
+            let whole = [1900.0, -50.0, 1920.0 + 4000.0, 3000.0];
+            assert_eq!(
+                area(screen_info(), whole).physical_rect(),
+                [0, 0, 2400, 1350],
+                "{transform:?}"
+            );
+        }
+        let screen_info = || {
+            screen(
+                Position { x: 1920, y: 0 },
+                mode,
+                size(1920, 1080),
+                Transform::Normal,
+            )
+        };
+        assert_eq!(
+            area(screen_info(), [3800.0, 1000.0, 4000.0, 1200.0]).physical_rect(),
+            [2350, 1250, 50, 100]
+        );
+        // an area outside of the output is empty
+        assert_eq!(
+            area(screen_info(), [0.0, 0.0, 100.0, 100.0]).physical_rect(),
+            [0, 0, 0, 100 * 5 / 4]
+        );
+    }
+
+    #[test]
+    fn boxes_from_str() {
+        let parse = |line: &str| BoxInfo::get_box_from_str(line).unwrap();
+        let rect = |info: &BoxInfo| [info.start_x, info.start_y, info.end_x, info.end_y];
+
+        let info = parse("0,0  10x10");
+        assert_eq!(rect(&info), [0.0, 0.0, 10.0, 10.0]);
+        assert_eq!((info.label, info.color.is_none()), (None, true));
+        assert_eq!(rect(&parse(" 1.5,-2\t3x4 ")), [1.5, -2.0, 4.5, 2.0]);
+
+        let info = parse("0,0 10x10  #ff000080  Firefox  web ");
+        let color = info.color.unwrap();
+        assert_eq!([color.r, color.g, color.b], [1.0, 0.0, 0.0]);
+        assert_eq!(info.label.as_deref(), Some("Firefox  web"));
+        let info = parse("0,0 10x10 #deadbeef");
+        assert_eq!((info.label, info.color.is_some()), (None, true));
+
+        // only a complete #rrggbbaa word is a color
+        for label in [
+            "#c0ffee notes",
+            "##deadbee notes",
+            "#deadbeef00 notes",
+            "#notacolor",
+        ] {
+            let info = parse(&format!("0,0 10x10 {label}"));
+            assert_eq!(info.label.as_deref(), Some(label));
+            assert!(info.color.is_none(), "{label}");
+        }
+        let info = parse("0,0 10x10 #00000000 #deadbeef notes");
+        assert_eq!(info.label.as_deref(), Some("#deadbeef notes"));
+
+        let error = |line: &str| BoxInfo::get_box_from_str(line).unwrap_err();
+        assert!(matches!(error("0,0"), BoxInfoError::InvalidBoxString(_)));
+        assert!(matches!(
+            error("0 10x10"),
+            BoxInfoError::InvalidBoxCoordsString(_)
+        ));
+        assert!(matches!(
+            error("0,0 10"),
+            BoxInfoError::InvalidBoxSizeString(_)
+        ));
+        assert!(matches!(error("a,0 1x1"), BoxInfoError::ParseFloatError(_)));
+        assert!(matches!(
+            error("NaN,0 1x1"),
+            BoxInfoError::InvalidBoxCoordsString(_)
+        ));
+        assert!(matches!(
+            error("0,0 infx1"),
+            BoxInfoError::InvalidBoxSizeString(_)
+        ));
+    }
+
+    fn point(x: f64, y: f64) -> Position<f64> {
+        Position { x, y }
+    }
+
+    fn limits(min: [f64; 2], max: [f64; 2]) -> SizeLimits {
+        SizeLimits {
+            min_width: min[0],
+            min_height: min[1],
+            max_width: max[0],
+            max_height: max[1],
+        }
+    }
+
+    /// [constrain] as `[start x, start y, end x, end y]`
+    fn constrained(
+        origin: [f64; 2],
+        target: [f64; 2],
+        ratio: Option<f64>,
+        limits: SizeLimits,
+        bounds: Option<[f64; 4]>,
+    ) -> [f64; 4] {
+        let (start, end) = constrain(
+            point(origin[0], origin[1]),
+            point(target[0], target[1]),
+            ratio,
+            &limits,
+            bounds,
+        );
+        [start.x, start.y, end.x, end.y]
+    }
+
+    #[test]
+    fn constrain_without_limits_follows_the_pointer() {
+        let free = SizeLimits::default();
+        assert_eq!(
+            constrained([10.0, 10.0], [50.0, 30.0], None, free, None),
+            [10.0, 10.0, 50.0, 30.0]
+        );
+        assert_eq!(
+            constrained([50.0, 30.0], [10.0, 10.0], None, free, None),
+            [50.0, 30.0, 10.0, 10.0]
+        );
+        // the ratio follows the shorter side
+        assert_eq!(
+            constrained([0.0, 0.0], [100.0, -100.0], Some(2.0), free, None),
+            [0.0, 0.0, 100.0, -50.0]
+        );
+    }
+
+    #[test]
+    fn constrain_grows_away_from_the_origin() {
+        let min = limits([100.0, 100.0], [f64::INFINITY; 2]);
+        assert_eq!(
+            constrained([200.0, 200.0], [210.0, 190.0], None, min, None),
+            [200.0, 200.0, 300.0, 100.0]
+        );
+        // a click grows right and down
+        assert_eq!(
+            constrained([200.0, 200.0], [200.0, 200.0], None, min, None),
+            [200.0, 200.0, 300.0, 300.0]
+        );
+        assert_eq!(
+            constrained([200.0, 200.0], [190.0, 210.0], Some(2.0), min, None),
+            [200.0, 200.0, 0.0, 300.0]
+        );
+    }
+
+    #[test]
+    fn constrain_stops_at_the_maximum_size() {
+        let max = limits([0.0, 0.0], [300.0, 200.0]);
+        assert_eq!(
+            constrained([10.0, 10.0], [1000.0, 1000.0], None, max, None),
+            [10.0, 10.0, 310.0, 210.0]
+        );
+        assert_eq!(
+            constrained([10.0, 10.0], [1000.0, 1000.0], Some(2.0), max, None),
+            [10.0, 10.0, 310.0, 160.0]
+        );
+        let both = limits([100.0, 100.0], [300.0, 200.0]);
+        assert_eq!(
+            constrained([10.0, 10.0], [1000.0, 1000.0], None, both, None),
+            [10.0, 10.0, 310.0, 210.0]
+        );
+    }
+
+    #[test]
+    fn constrain_moves_back_into_the_bounds() {
+        let min = limits([100.0, 100.0], [f64::INFINITY; 2]);
+        let bounds = Some([0.0, 0.0, 500.0, 500.0]);
+        assert_eq!(
+            constrained([450.0, 450.0], [460.0, 460.0], None, min, bounds),
+            [400.0, 400.0, 500.0, 500.0]
+        );
+        assert_eq!(
+            constrained([450.0, 450.0], [460.0, 460.0], Some(1.0), min, bounds),
+            [400.0, 400.0, 500.0, 500.0]
+        );
+        assert_eq!(
+            constrained([20.0, 20.0], [10.0, 10.0], None, min, bounds),
+            [100.0, 100.0, 0.0, 0.0]
+        );
+        // an area wider than the bounds is cut, then moved back
+        assert_eq!(
+            constrained(
+                [100.0, 100.0],
+                [700.0, 50.0],
+                None,
+                SizeLimits::default(),
+                bounds
+            ),
+            [0.0, 100.0, 500.0, 50.0]
+        );
+    }
+
+    #[test]
+    fn constrain_bounds_win_over_the_minimum_size() {
+        let min = limits([200.0, 200.0], [f64::INFINITY; 2]);
+        assert_eq!(
+            constrained(
+                [20.0, 20.0],
+                [30.0, 30.0],
+                None,
+                min,
+                Some([0.0, 0.0, 100.0, 1000.0])
+            ),
+            [0.0, 20.0, 100.0, 220.0]
+        );
+        assert_eq!(
+            constrained(
+                [10.0, 10.0],
+                [20.0, 20.0],
+                None,
+                min,
+                Some([0.0, 0.0, 100.0, 50.0])
+            ),
+            [0.0, 0.0, 100.0, 50.0]
+        );
+        assert_eq!(
+            constrained(
+                [50.0, 50.0],
+                [60.0, 60.0],
+                Some(2.0),
+                min,
+                Some([0.0, 0.0, 100.0, 1000.0])
+            ),
+            [0.0, 50.0, 100.0, 100.0]
+        );
+        assert_eq!(
+            constrained(
+                [0.0, 0.0],
+                [1000.0, 1000.0],
+                Some(16.0 / 9.0),
+                SizeLimits::default(),
+                Some([0.0, 0.0, 100.0, 1000.0])
+            ),
+            [0.0, 0.0, 100.0, 56.25]
+        );
+    }
+
+    #[test]
+    fn fit_span_moves_into_the_range() {
+        assert_eq!(fit_span(10.0, 20.0, 0.0, 100.0), (10.0, 20.0));
+        assert_eq!(fit_span(-5.0, 5.0, 0.0, 100.0), (0.0, 10.0));
+        assert_eq!(fit_span(95.0, 105.0, 0.0, 100.0), (90.0, 100.0));
+        assert_eq!(fit_span(105.0, 95.0, 0.0, 100.0), (100.0, 90.0));
+        assert_eq!(fit_span(0.0, 100.0, 0.0, 100.0), (0.0, 100.0));
+        assert_eq!(
+            fit_span(-1e9, 1e9, f64::NEG_INFINITY, f64::INFINITY),
+            (-1e9, 1e9)
+        );
+    }
+
+    #[test]
+    fn clamp_point_keeps_the_pointer_in_the_bounds() {
+        let clamped = |x, y, bounds| {
+            let point = clamp_point(point(x, y), bounds);
+            [point.x, point.y]
+        };
+        let bounds = Some([0.0, 10.0, 100.0, 110.0]);
+        assert_eq!(clamped(-5.0, 200.0, None), [-5.0, 200.0]);
+        assert_eq!(clamped(50.0, 50.0, bounds), [50.0, 50.0]);
+        assert_eq!(clamped(-5.0, 200.0, bounds), [0.0, 110.0]);
+        assert_eq!(clamped(150.0, 0.0, bounds), [100.0, 10.0]);
+    }
 }
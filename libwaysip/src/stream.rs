@@ -0,0 +1,177 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    os::fd::OwnedFd,
+};
+
+use crate::state::BoxInfo;
+
+/// the longest snapshot kept, 16 MiB, the longer lines are skipped
+const MAX_SNAPSHOT: usize = 16 << 20;
+
+/// Parse a snapshot of the predefined boxes, a line of the stream. `None` skips the line
+pub type BoxSnapshotParser = fn(&str) -> Option<Vec<BoxInfo>>;
+
+/// A stream of snapshots of the predefined boxes, one per line, read while the overlay is shown
+#[derive(Debug)]
+pub(crate) struct BoxStream {
+    pub file: File,
+    parse: BoxSnapshotParser,
+    /// the start of a line not yet complete
+    pending: Vec<u8>,
+    /// the longest line kept in `pending`
+    max_snapshot: usize,
+    /// whether the line being read is too long, it is skipped up to its newline
+    skipping: bool,
+    /// whether the writer closed the stream, the boxes then stay as they are
+    pub closed: bool,
+}
+
+impl BoxStream {
+    pub fn new(fd: OwnedFd, parse: BoxSnapshotParser) -> Self {
+        Self {
+            file: File::from(fd),
+            parse,
+            pending: Vec::new(),
+            max_snapshot: MAX_SNAPSHOT,
+            skipping: false,
+            closed: false,
+        }
+    }
+
+    /// Read what is available, after a poll, and return the last complete snapshot
+    pub fn read(&mut self) -> Option<Vec<BoxInfo>> {
+        let mut buffer = [0; 4096];
+        let mut data = match self.file.read(&mut buffer) {
+            Ok(0) => {
+                // the last line may lack its newline
+                self.closed = true;
+                &b"\n"[..]
+            }
+            Ok(read) => &buffer[..read],
+            Err(e) if e.kind() == ErrorKind::Interrupted || e.kind() == ErrorKind::WouldBlock => {
+                return None;
+            }
+            Err(_) => {
+                self.closed = true;
+                return None;
+            }
+        };
+        if self.skipping {
+            let end = data.iter().position(|byte| *byte == b'\n')?;
+            self.skipping = false;
+            data = &data[end + 1..];
+        }
+        self.pending.extend_from_slice(data);
+        let snapshot = self
+            .pending
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .and_then(|end| {
+                let lines: Vec<u8> = self.pending.drain(..=end).collect();
+                // only the newest snapshot matters, the older ones are already stale
+                String::from_utf8_lossy(&lines)
+                    .lines()
+                    .rev()
+                    .filter(|line| !line.trim().is_empty())
+                    .find_map(self.parse)
+            });
+        if self.pending.len() > self.max_snapshot {
+            self.pending = Vec::new();
+            self.skipping = true;
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    use super::*;
+
+    /// boxes separated by `;`, a line with an invalid box is skipped
+    fn parse(line: &str) -> Option<Vec<BoxInfo>> {
+        line.split(';')
+            .map(|text| BoxInfo::get_box_from_str(text).ok())
+            .collect()
+    }
+
+    fn stream() -> (UnixStream, BoxStream) {
+        let (writer, reader) = UnixStream::pair().unwrap();
+        (writer, BoxStream::new(reader.into(), parse))
+    }
+
+    fn rects(boxes: Option<Vec<BoxInfo>>) -> Option<Vec<[f64; 4]>> {
+        boxes.map(|boxes| {
+            boxes
+                .iter()
+                .map(|info| [info.start_x, info.start_y, info.end_x, info.end_y])
+                .collect()
+        })
+    }
+
+    #[test]
+    fn partial_line_across_reads() {
+        let (mut writer, mut stream) = stream();
+        writer.write_all(b"0,0 1x1;2,2 ").unwrap();
+        assert_eq!(rects(stream.read()), None);
+        writer.write_all(b"3x3\n5,5").unwrap();
+        assert_eq!(
+            rects(stream.read()),
+            Some(vec![[0.0, 0.0, 1.0, 1.0], [2.0, 2.0, 5.0, 5.0]])
+        );
+        writer.write_all(b" 1x1\n").unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[5.0, 5.0, 6.0, 6.0]]));
+        assert!(!stream.closed);
+    }
+
+    #[test]
+    fn only_the_last_snapshot_of_a_read_is_kept() {
+        let (mut writer, mut stream) = stream();
+        writer
+            .write_all(b"0,0 1x1\n5,5 1x1;6,6 1x1\n\n7,7 2x2\n8,8")
+            .unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[7.0, 7.0, 9.0, 9.0]]));
+        // an invalid last line falls back to the newest valid one
+        writer.write_all(b" 1x1\ninvalid\n").unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[8.0, 8.0, 9.0, 9.0]]));
+        writer.write_all(b"invalid\n").unwrap();
+        assert_eq!(rects(stream.read()), None);
+    }
+
+    #[test]
+    fn eof_without_trailing_newline() {
+        let (mut writer, mut stream) = stream();
+        writer.write_all(b"0,0 1x1\n1,1 2x2").unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[0.0, 0.0, 1.0, 1.0]]));
+        assert!(!stream.closed);
+        drop(writer);
+        assert_eq!(rects(stream.read()), Some(vec![[1.0, 1.0, 3.0, 3.0]]));
+        assert!(stream.closed);
+    }
+
+    #[test]
+    fn lines_too_long_are_skipped() {
+        let (mut writer, mut stream) = stream();
+        stream.max_snapshot = 16;
+        writer.write_all(b"0,0 1x1;1,1 1x1;2,2 1x1;").unwrap();
+        assert_eq!(rects(stream.read()), None);
+        assert!(stream.pending.is_empty());
+        // the rest of the long line is dropped, the next one is read
+        writer.write_all(b"3,3 1x1;4,4 1x1\n5,5 ").unwrap();
+        assert_eq!(rects(stream.read()), None);
+        writer.write_all(b"1x1\n").unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[5.0, 5.0, 6.0, 6.0]]));
+
+        // a long line cut by the end of the stream is dropped as well
+        writer
+            .write_all(b"6,6 1x1\n0,0 1x1;1,1 1x1;2,2 1x1;")
+            .unwrap();
+        assert_eq!(rects(stream.read()), Some(vec![[6.0, 6.0, 7.0, 7.0]]));
+        assert!(stream.skipping);
+        drop(writer);
+        assert_eq!(rects(stream.read()), None);
+        assert!(stream.closed);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::ColorError;
+use crate::{error::ColorError, format::Template};
 
 /// Describe the point
 #[derive(Debug, Copy, Clone)]
@@ -61,10 +61,139 @@ impl Color {
         };
         Ok(color)
     }
+
+    /// the `#rrggbbaa` form of the color, the inverse of [Color::hex_to_color]
+    pub fn to_hex(&self) -> String {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            channel(self.r),
+            channel(self.g),
+            channel(self.b),
+            channel(self.a)
+        )
+    }
+}
+
+/// The label drawn next to the selection by default
+pub const DEFAULT_LABEL_FORMAT: &str = "%x,%y, %wx%h";
+
+/// The info card shown on the hovered output when selecting a screen, by default
+pub const DEFAULT_SCREEN_CARD_FORMAT: &str =
+    "%o  %d\n%wx%h logical, %Lx%T physical\nscale %s, %{r:.2} Hz, %t\npos: %x, %y";
+
+/// Where the info card is drawn on the hovered output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CardPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// The corner of the selection the label is drawn at. The label flips to the other side when it
+/// would leave the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LabelCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Where the border is drawn, relative to the edge of the selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BorderPosition {
+    /// inside the selection, so it does not cover the pixels around it
+    Inner,
+    /// centered on the edge
+    #[default]
+    Center,
+    /// outside the selection, so it does not cover the selected pixels
+    Outer,
+}
+
+/// Which of the predefined boxes under the pointer is picked first, scrolling goes through the
+/// others in the same order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoxPolicy {
+    /// the box with the smallest area, so the innermost of nested boxes
+    #[default]
+    Smallest,
+    /// the last box of the input, drawn above the others
+    Topmost,
+    /// the box with the highest [BoxInfo::z](crate::BoxInfo::z), the last of the input on ties
+    ZOrder,
+}
+
+/// The smallest and the largest size of the selection, it is clamped while dragging
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeLimits {
+    pub min_width: f64,
+    pub min_height: f64,
+    pub max_width: f64,
+    pub max_height: f64,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            min_width: 0.0,
+            min_height: 0.0,
+            max_width: f64::INFINITY,
+            max_height: f64::INFINITY,
+        }
+    }
+}
+
+/// The rect the selection can't leave, it is clamped while dragging
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bounds {
+    /// the output the selection starts on
+    Output,
+    /// the output with this name
+    NamedOutput(String),
+    /// the first predefined box with this label
+    Box(String),
+    /// a rect in the global coordinates
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+/// The point of a fixed-size selection held at the pointer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StampAnchor {
+    #[default]
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// The guides drawn inside the selection, for framing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompositionGuides {
+    /// rule-of-thirds lines
+    pub thirds: bool,
+    /// a cross at the center
+    pub center: bool,
+    /// the title-safe area, 90% of the selection
+    pub title_safe: bool,
+    /// the action-safe area, 93% of the selection
+    pub action_safe: bool,
 }
 
-/// Current style of the info
+/// Current style of the info, build it from `Style::default()`
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Style {
     pub background_color: Color,
     pub foreground_color: Color,
@@ -73,6 +202,38 @@ pub struct Style {
     pub border_weight: f64,
     pub font_size: i32,
     pub font_name: String,
+    /// the text of the label, `None` to hide it
+    pub label: Option<Template>,
+    pub label_corner: LabelCorner,
+    /// the color of the pill behind the label
+    pub label_background_color: Color,
+    /// lines through the pointer across all the outputs
+    pub crosshair: bool,
+    /// rulers along the edges of the outputs
+    pub rulers: bool,
+    pub composition_guides: CompositionGuides,
+    /// the dash pattern of the border, solid when empty
+    pub border_dash: Vec<f64>,
+    pub corner_radius: f64,
+    /// animate the dashes of the border
+    pub marching_ants: bool,
+    pub border_position: BorderPosition,
+    /// the background of the outputs without the selection or the pointer, `None` to keep
+    /// background_color
+    pub inactive_color: Option<Color>,
+    /// the fill of the hovered output when selecting a screen, `None` to use foreground_color
+    pub highlight_color: Option<Color>,
+    /// outline the hovered output when selecting a screen
+    pub outline_hovered: bool,
+    /// the text of the info card of the hovered output when selecting a screen, `None` to hide it
+    pub screen_card: Option<Template>,
+    pub screen_card_position: CardPosition,
+    /// a status line with the mode and the main keys on the focused output
+    pub status_line: bool,
+    /// the fill of the predefined box under the pointer, `None` to use foreground_color
+    pub box_hover_color: Option<Color>,
+    /// the border of the selection while it is clamped to the size limits or the bounds
+    pub clamp_color: Color,
 }
 
 impl Default for Style {
@@ -105,6 +266,39 @@ impl Default for Style {
             border_weight: 1.0,
             font_size: 12,
             font_name: "Sans".to_string(),
+            label: Some(
+                Template::parse(DEFAULT_LABEL_FORMAT).expect("the default label should be valid"),
+            ),
+            label_corner: LabelCorner::BottomRight,
+            label_background_color: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            }, // #00000000
+            crosshair: false,
+            rulers: false,
+            composition_guides: CompositionGuides::default(),
+            border_dash: Vec::new(),
+            corner_radius: 0.0,
+            marching_ants: false,
+            border_position: BorderPosition::Center,
+            inactive_color: None,
+            highlight_color: None,
+            outline_hovered: false,
+            screen_card: Some(
+                Template::parse(DEFAULT_SCREEN_CARD_FORMAT)
+                    .expect("the default screen card should be valid"),
+            ),
+            screen_card_position: CardPosition::TopLeft,
+            status_line: false,
+            box_hover_color: None,
+            clamp_color: Color {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0,
+                a: 1.0,
+            }, // #ff0000ff
         }
     }
 }
@@ -3,6 +3,7 @@
   rustPlatform,
   pkg-config,
   glib,
+  libxkbcommon,
   pango,
   ...
 }:
@@ -20,6 +21,7 @@ rustPlatform.buildRustPackage rec {
 
   buildInputs = [
     glib
+    libxkbcommon
     pango
   ];
 }
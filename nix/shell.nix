@@ -20,6 +20,7 @@ pkgs.mkShell rec {
   ];
   buildInputs = with pkgs; [
     glib
+    libxkbcommon
     pango
   ];
 }
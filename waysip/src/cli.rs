@@ -1,14 +1,16 @@
-#[cfg(feature = "completions")]
-use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, ffi::OsString, os::fd::RawFd, path::PathBuf};
 #[cfg(feature = "logger")]
 use tracing::Level;
 
 use clap::{
-    Parser,
+    ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum,
     builder::{
-        Styles,
+        BoolishValueParser, Styles,
         styling::{AnsiColor, Effects},
     },
+    error::ErrorKind,
+    parser::ValueSource,
 };
 
 /// Shell variants for completion generation.
@@ -23,6 +25,145 @@ pub enum Shell {
     Nushell,
 }
 
+/// Structured output formats, an alternative to the `-f` template.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A single JSON object.
+    Json,
+    /// `WAYSIP_*` variable assignments, safe to `eval`.
+    Shell,
+    /// A header line followed by a single record.
+    Csv,
+}
+
+/// Corner of the selection the label is drawn at.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LabelCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl From<LabelCorner> for libwaysip::LabelCorner {
+    fn from(corner: LabelCorner) -> Self {
+        match corner {
+            LabelCorner::TopLeft => Self::TopLeft,
+            LabelCorner::TopRight => Self::TopRight,
+            LabelCorner::BottomLeft => Self::BottomLeft,
+            LabelCorner::BottomRight => Self::BottomRight,
+        }
+    }
+}
+
+/// Position of the info card on the hovered output.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CardPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl From<CardPosition> for libwaysip::CardPosition {
+    fn from(position: CardPosition) -> Self {
+        match position {
+            CardPosition::TopLeft => Self::TopLeft,
+            CardPosition::TopRight => Self::TopRight,
+            CardPosition::BottomLeft => Self::BottomLeft,
+            CardPosition::BottomRight => Self::BottomRight,
+            CardPosition::Center => Self::Center,
+        }
+    }
+}
+
+/// Position of the border, relative to the edge of the selection.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderPosition {
+    /// Inside the selection.
+    Inner,
+    /// Centered on the edge.
+    Center,
+    /// Outside the selection, the selected pixels stay uncovered.
+    Outer,
+}
+
+impl From<BorderPosition> for libwaysip::BorderPosition {
+    fn from(position: BorderPosition) -> Self {
+        match position {
+            BorderPosition::Inner => Self::Inner,
+            BorderPosition::Center => Self::Center,
+            BorderPosition::Outer => Self::Outer,
+        }
+    }
+}
+
+/// Composition guides drawn inside the selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Guide {
+    /// Rule-of-thirds lines.
+    Thirds,
+    /// A cross at the center.
+    Center,
+    /// The title-safe area, 90% of the selection.
+    TitleSafe,
+    /// The action-safe area, 93% of the selection.
+    ActionSafe,
+    /// Both safe areas.
+    Safe,
+}
+
+/// How the predefined box under the pointer is picked when boxes overlap.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoxPolicy {
+    /// The box with the smallest area.
+    Smallest,
+    /// The box given last.
+    Topmost,
+    /// The box with the highest `z` of the JSON input.
+    ZOrder,
+}
+
+impl From<BoxPolicy> for libwaysip::BoxPolicy {
+    fn from(policy: BoxPolicy) -> Self {
+        match policy {
+            BoxPolicy::Smallest => Self::Smallest,
+            BoxPolicy::Topmost => Self::Topmost,
+            BoxPolicy::ZOrder => Self::ZOrder,
+        }
+    }
+}
+
+/// The point of the `-z` area held at the pointer.
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StampAnchor {
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl From<StampAnchor> for libwaysip::StampAnchor {
+    fn from(anchor: StampAnchor) -> Self {
+        match anchor {
+            StampAnchor::Center => Self::Center,
+            StampAnchor::TopLeft => Self::TopLeft,
+            StampAnchor::TopRight => Self::TopRight,
+            StampAnchor::BottomLeft => Self::BottomLeft,
+            StampAnchor::BottomRight => Self::BottomRight,
+        }
+    }
+}
+
 fn get_styles() -> Styles {
     Styles::styled()
         .header(AnsiColor::Yellow.on_default() | Effects::BOLD)
@@ -43,38 +184,195 @@ pub struct Cli {
 
     // ─── Colors ───────────────────────────────────────────────────────────────
     /// Set background color.
-    #[arg(short = 'b', value_name = "#rrggbbaa/rrggbbaa")]
+    #[arg(
+        short = 'b',
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_BACKGROUND"
+    )]
     pub background: Option<String>,
 
     /// Set border and text color.
-    #[arg(short = 'c', value_name = "#rrggbbaa/rrggbbaa")]
+    #[arg(
+        short = 'c',
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_BORDER_COLOR"
+    )]
     pub border_color: Option<String>,
 
     /// Set selection color.
-    #[arg(short = 's', value_name = "#rrggbbaa/rrggbbaa")]
+    #[arg(
+        short = 's',
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_SELECTION_COLOR"
+    )]
     pub selection_color: Option<String>,
 
     /// Set option box color.
-    #[arg(short = 'B', value_name = "#rrggbbaa/rrggbbaa")]
+    #[arg(
+        short = 'B',
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_BOX_COLOR"
+    )]
     pub box_color: Option<String>,
 
+    /// Set the fill of the hovered predefined box.
+    #[arg(
+        long,
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_BOX_HOVER_COLOR"
+    )]
+    pub box_hover_color: Option<String>,
+
+    /// Set the border of the selection while it is clamped to `--min-width`, `--max-width`,
+    /// `--min-height`, `--max-height` or `--bounds`.
+    #[arg(long, value_name = "#rrggbbaa/rrggbbaa", env = "WAYSIP_CLAMP_COLOR")]
+    pub clamp_color: Option<String>,
+
+    /// Set the background of the outputs without the selection or the pointer.
+    #[arg(long, value_name = "#rrggbbaa/rrggbbaa", env = "WAYSIP_INACTIVE_COLOR")]
+    pub inactive_color: Option<String>,
+
+    /// Black out the outputs without the selection or the pointer.
+    #[arg(long, env = "WAYSIP_BLACKOUT")]
+    pub blackout: Option<bool>,
+
+    /// Set the fill of the hovered output when selecting an output.
+    #[arg(
+        long,
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_HIGHLIGHT_COLOR"
+    )]
+    pub highlight_color: Option<String>,
+
+    /// Outline the hovered output when selecting an output.
+    #[arg(long, env = "WAYSIP_OUTLINE_OUTPUT")]
+    pub outline_output: Option<bool>,
+
     // ─── Typography & border ─────────────────────────────────────────────────
     /// Set the font family for the dimensions.
-    #[arg(short = 'F', value_name = "string")]
+    #[arg(short = 'F', value_name = "string", env = "WAYSIP_FONT_NAME")]
     pub font_name: Option<String>,
 
     /// Set font size.
-    #[arg(short = 'S', value_name = "integer")]
+    #[arg(short = 'S', value_name = "integer", env = "WAYSIP_FONT_SIZE")]
     pub font_size: Option<i32>,
 
     /// Set border weight.
-    #[arg(short = 'w', value_name = "float")]
+    #[arg(short = 'w', value_name = "float", env = "WAYSIP_BORDER_WEIGHT")]
     pub border_weight: Option<String>,
 
+    /// Set the dash pattern of the border, as comma separated lengths.
+    #[arg(
+        long,
+        value_name = "float,...",
+        value_delimiter = ',',
+        env = "WAYSIP_BORDER_DASH"
+    )]
+    pub border_dash: Option<Vec<f64>>,
+
+    /// Round the corners of the selection.
+    #[arg(long, value_name = "float", env = "WAYSIP_CORNER_RADIUS")]
+    pub corner_radius: Option<f64>,
+
+    /// Animate the dashes of the border.
+    #[arg(long, env = "WAYSIP_MARCHING_ANTS")]
+    pub marching_ants: Option<bool>,
+
+    /// Draw the border inside, on, or outside the edge of the selection.
+    #[arg(long, value_name = "POSITION", env = "WAYSIP_BORDER_POSITION")]
+    pub border_position: Option<BorderPosition>,
+
+    // ─── Label ──────────────────────────────────────────────────────────────
+    /// Set the label drawn next to the selection, `%x,%y, %wx%h` by default.
+    /// Takes the placeholders of `-f`, an empty label hides it.
+    #[arg(
+        long,
+        value_name = "string",
+        env = "WAYSIP_LABEL",
+        verbatim_doc_comment
+    )]
+    pub label: Option<String>,
+
+    /// Hide the label, over `--label`.
+    #[arg(long)]
+    pub no_label: bool,
+
+    /// Set the corner of the selection the label is drawn at, it flips to stay on the output.
+    #[arg(long, value_name = "CORNER", env = "WAYSIP_LABEL_CORNER")]
+    pub label_corner: Option<LabelCorner>,
+
+    /// Set the background color of the label.
+    #[arg(
+        long,
+        value_name = "#rrggbbaa/rrggbbaa",
+        env = "WAYSIP_LABEL_BACKGROUND"
+    )]
+    pub label_background: Option<String>,
+
+    /// Set the info card of the hovered output in `-o`/`-i` mode, an empty card hides it.
+    /// Takes the placeholders of `-f`, for the whole output.
+    #[arg(
+        long,
+        value_name = "string",
+        env = "WAYSIP_SCREEN_CARD",
+        verbatim_doc_comment
+    )]
+    pub screen_card: Option<String>,
+
+    /// Set the position of the info card on the hovered output.
+    #[arg(long, value_name = "POSITION", env = "WAYSIP_SCREEN_CARD_POSITION")]
+    pub screen_card_position: Option<CardPosition>,
+
+    // ─── Guides ─────────────────────────────────────────────────────────────
+    /// Draw lines through the pointer across all outputs.
+    #[arg(long, env = "WAYSIP_CROSSHAIR")]
+    pub crosshair: Option<bool>,
+
+    /// Draw pixel rulers along the output edges, with the position of the pointer.
+    #[arg(long, env = "WAYSIP_RULERS")]
+    pub rulers: Option<bool>,
+
+    /// Draw composition guides inside the selection, as a comma separated list.
+    #[arg(
+        long,
+        value_name = "GUIDES",
+        value_delimiter = ',',
+        env = "WAYSIP_GUIDES"
+    )]
+    pub guides: Option<Vec<Guide>>,
+
+    /// Show a status line with the mode, the aspect ratio and the main keys on the focused
+    /// output. `?` or F1 shows or hides the help with every key.
+    #[arg(long, env = "WAYSIP_STATUS_LINE")]
+    pub status_line: Option<bool>,
+
     // ─── Output format ───────────────────────────────────────────────────────
-    /// Set output format.
-    #[arg(short = 'f', value_name = "string", default_value = "%x,%y %wx%h\n")]
-    pub format: String,
+    /// Set output format, `%x,%y %wx%h\n` by default.
+    /// Placeholders: %x %y %w %h (global), %X %Y %W %H (output-relative),
+    /// %o/%l output name, %d description, %L %T output mode size,
+    /// %m make, %M model, %s scale, %r refresh rate (Hz), %t transform,
+    /// %P physical size (mm),
+    /// %px %py %pw %ph (output-relative, in physical buffer pixels),
+    /// %b label of the selected box of `-r`.
+    /// Named fields: %{name} or %{name:spec} with spec = [<|>|^][0][width][.precision],
+    /// arithmetic like %{w/2}, nx ny nw nh for 0-1 coordinates relative to the output,
+    /// and conditional text with %{?screen:then|else} or %{?!screen:text}.
+    #[arg(
+        short = 'f',
+        value_name = "string",
+        env = "WAYSIP_FORMAT",
+        verbatim_doc_comment
+    )]
+    pub format: Option<String>,
+
+    /// Print the complete selection in a structured format instead of `-f`.
+    #[arg(long, value_name = "FORMAT", env = "WAYSIP_OUTPUT_FORMAT")]
+    pub output_format: Option<OutputFormat>,
+
+    /// Use the output format of a preset: grim, wf-recorder, ffmpeg, gpu-screen-recorder,
+    /// imagemagick, or one from the `[presets]` table of the config file.
+    #[arg(long, value_name = "NAME", env = "WAYSIP_PRESET")]
+    pub preset: Option<String>,
 
     // ─── Selection mode ──────────────────────────────────────────────────────
     /// Select a single point.
@@ -82,7 +380,7 @@ pub struct Cli {
     pub point: bool,
 
     /// Display dimensions of selection.
-    #[arg(short = 'd', conflicts_with_all = ["point", "screen", "boxes"])]
+    #[arg(short = 'd', conflicts_with_all = ["point", "screen"])]
     pub dimensions: bool,
 
     /// Get screen information
@@ -93,21 +391,379 @@ pub struct Cli {
     #[arg(short = 'o', conflicts_with_all = ["point", "screen", "boxes"])]
     pub output: bool,
 
-    /// Restrict selection to predefined boxes.
-    #[arg(short = 'r', conflicts_with_all = ["point", "dimensions", "output", "screen"])]
+    /// Restrict selection to predefined boxes, read from stdin.
+    /// One per line as `x,y WIDTHxHEIGHT [#rrggbbaa] [label]`, or a JSON array of
+    /// {"x", "y", "width", "height", "label", "color", "z"} objects.
+    /// With -d, a click selects the box under the pointer and a drag selects an area.
+    #[arg(short = 'r', verbatim_doc_comment, conflicts_with_all = ["point", "output", "screen"])]
     pub boxes: bool,
 
-    /// Force aspect ratio.
+    /// Restrict selection to the visible windows, listed from the IPC of sway, i3, Hyprland or
+    /// niri, with their title as label. With -d, a drag selects an area.
+    #[cfg(feature = "providers")]
     #[arg(
-        short = 'a',
-        value_name = "width:height",
-        conflicts_with_all = ["point", "screen", "output", "boxes"]
+        long,
+        conflicts_with_all = ["point", "output", "screen", "boxes", "identify", "fixed_size", "preview"]
     )]
+    pub windows: bool,
+
+    /// Pick the predefined box under the pointer when boxes overlap. The wheel cycles through
+    /// the other boxes under the pointer.
+    #[arg(long, value_name = "policy", env = "WAYSIP_BOX_POLICY")]
+    pub box_policy: Option<BoxPolicy>,
+
+    /// Show the overlay right away and keep reading boxes from stdin while selecting, one JSON
+    /// array of boxes per line, each line replacing the boxes.
+    #[arg(long, requires = "boxes", conflicts_with = "preview")]
+    pub box_stream: bool,
+
+    /// Read the box stream of `--box-stream` from this file descriptor instead of stdin.
+    #[arg(
+        long,
+        value_name = "fd",
+        value_parser = clap::value_parser!(i32).range(0..),
+        requires = "boxes",
+        conflicts_with = "preview"
+    )]
+    pub box_fd: Option<RawFd>,
+
+    /// Place an area of this size with a click, it follows the pointer until then. The wheel
+    /// scales it, keeping its aspect ratio.
+    #[arg(
+        short = 'z',
+        value_name = "WxH",
+        conflicts_with_all = ["point", "dimensions", "output", "screen", "boxes", "identify"]
+    )]
+    pub fixed_size: Option<String>,
+
+    /// Hold the `-z` area at the pointer by this point.
+    #[arg(long, value_name = "anchor", env = "WAYSIP_STAMP_ANCHOR")]
+    pub stamp_anchor: Option<StampAnchor>,
+
+    /// Show the index, name, mode, scale and position of every output, until a key or a button
+    /// is pressed.
+    #[arg(long, conflicts_with_all = ["point", "dimensions", "output", "screen", "boxes"])]
+    pub identify: bool,
+
+    /// Close after this many seconds, as if the selection was canceled. 5 by default with
+    /// `--identify`.
+    #[arg(long, value_name = "seconds", env = "WAYSIP_TIMEOUT")]
+    pub timeout: Option<f64>,
+
+    /// Force aspect ratio.
+    #[arg(short = 'a', value_name = "width:height", env = "WAYSIP_ASPECT_RATIO")]
     pub aspect_ratio: Option<String>,
 
+    /// Go through these aspect ratios with the wheel and Tab while dragging, as a comma separated
+    /// list of `width:height` or `free`. The selection starts with `-a`, or free without it.
+    #[arg(
+        long,
+        value_name = "width:height|free,...",
+        value_delimiter = ',',
+        env = "WAYSIP_ASPECT_PRESETS"
+    )]
+    pub aspect_presets: Option<Vec<String>>,
+
+    /// Keep the selection at least this wide, it is clamped while dragging.
+    #[arg(long, value_name = "px", env = "WAYSIP_MIN_WIDTH")]
+    pub min_width: Option<f64>,
+
+    /// Keep the selection at least this high.
+    #[arg(long, value_name = "px", env = "WAYSIP_MIN_HEIGHT")]
+    pub min_height: Option<f64>,
+
+    /// Keep the selection at most this wide.
+    #[arg(long, value_name = "px", env = "WAYSIP_MAX_WIDTH")]
+    pub max_width: Option<f64>,
+
+    /// Keep the selection at most this high.
+    #[arg(long, value_name = "px", env = "WAYSIP_MAX_HEIGHT")]
+    pub max_height: Option<f64>,
+
+    /// Keep the selection inside `output`, the output it starts on, `output:NAME`, `box:LABEL`,
+    /// the first predefined box with this label, or `x,y WIDTHxHEIGHT`.
+    #[arg(long, value_name = "bounds", env = "WAYSIP_BOUNDS")]
+    pub bounds: Option<String>,
+
+    // ─── Configuration ───────────────────────────────────────────────────────
+    /// Use the options of a profile from the `[profiles]` table of the config file.
+    /// Precedence: command line, environment, profile, then the top level of the config file.
+    #[arg(
+        long,
+        value_name = "NAME",
+        env = "WAYSIP_PROFILE",
+        verbatim_doc_comment
+    )]
+    pub profile: Option<String>,
+
+    /// Print the effective settings as a config file and exit.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Draw the overlay into this PNG and exit, without a compositor: a sample selection with its
+    /// label and boxes, the boxes of `--boxes` when given, next to a hovered output with its card.
+    #[arg(long, value_name = "PATH")]
+    pub preview: Option<PathBuf>,
+
+    /// Size of each of the two outputs drawn by `--preview`.
+    #[arg(
+        long,
+        value_name = "WxH",
+        default_value = "800x450",
+        requires = "preview"
+    )]
+    pub preview_size: String,
+
     // ─── Global options ───────────────────────────────────────────────────────
     /// Log level written to stderr.
     #[cfg(feature = "logger")]
-    #[arg(long)]
+    #[arg(long, env = "WAYSIP_LOG_LEVEL")]
     pub log_level: Option<Level>,
 }
+
+/// The flags taking an optional `=true` or `=false`, so that the command line or the environment
+/// can turn off what a lower layer turns on: `--crosshair` is `--crosshair=true`, and
+/// `WAYSIP_CROSSHAIR=0` turns it off over the config file.
+const TOGGLES: &[&str] = &[
+    "blackout",
+    "outline_output",
+    "marching_ants",
+    "crosshair",
+    "rulers",
+    "status_line",
+];
+
+/// The command of [Cli], with the [TOGGLES] taking an optional value.
+pub(crate) fn command() -> Command {
+    Cli::command().mut_args(|arg| match TOGGLES.contains(&arg.get_id().as_str()) {
+        true => arg
+            .value_name("bool")
+            .value_parser(BoolishValueParser::new())
+            .num_args(0..=1)
+            .default_missing_value("true")
+            .require_equals(true),
+        false => arg,
+    })
+}
+
+/// The options that replace each other, as pairs of argument ids. They are not clap conflicts, as
+/// clap would also refuse a value from the environment next to one from the command line.
+const EXCLUSIVE: &[(&str, &str)] = &[
+    ("format", "output_format"),
+    ("format", "preset"),
+    ("output_format", "preset"),
+    ("aspect_ratio", "point"),
+    ("aspect_ratio", "screen"),
+    ("aspect_ratio", "output"),
+    ("aspect_ratio", "boxes"),
+    ("blackout", "inactive_color"),
+];
+
+impl Cli {
+    /// Parse the command line and the `WAYSIP_*` variables, exiting on errors.
+    pub(crate) fn parse_layered() -> Self {
+        Self::try_parse_layered_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args` and the `WAYSIP_*` variables. Of two options replacing each other, the one
+    /// from the command line wins over the one from the environment, and both from the same place
+    /// are an error.
+    pub(crate) fn try_parse_layered_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut command = command();
+        let matches = command.try_get_matches_from_mut(args)?;
+        let mut cli = Self::from_arg_matches(&matches).map_err(|e| e.format(&mut command))?;
+        for (first, second) in EXCLUSIVE {
+            let (Some(first_source), Some(second_source)) =
+                (source(&matches, first), source(&matches, second))
+            else {
+                continue;
+            };
+            match first_source.cmp(&second_source) {
+                Ordering::Greater => cli.clear(second),
+                Ordering::Less => cli.clear(first),
+                Ordering::Equal => {
+                    let message = format!(
+                        "the argument '{}' cannot be used with '{}'",
+                        flag(&command, first),
+                        flag(&command, second)
+                    );
+                    return Err(command.error(ErrorKind::ArgumentConflict, message));
+                }
+            }
+        }
+        Ok(cli)
+    }
+
+    /// Forget the option `id`, replaced by one from the command line.
+    fn clear(&mut self, id: &str) {
+        match id {
+            "format" => self.format = None,
+            "output_format" => self.output_format = None,
+            "preset" => self.preset = None,
+            "aspect_ratio" => self.aspect_ratio = None,
+            "blackout" => self.blackout = None,
+            "inactive_color" => self.inactive_color = None,
+            _ => unreachable!("`{id}` is not read from the environment"),
+        }
+    }
+}
+
+/// Where the option `id` was given, `None` when it was not, or is a flag given as false.
+fn source(matches: &ArgMatches, id: &str) -> Option<ValueSource> {
+    if let Ok(Some(false)) = matches.try_get_one::<bool>(id) {
+        return None;
+    }
+    matches
+        .value_source(id)
+        .filter(|source| *source != ValueSource::DefaultValue)
+}
+
+/// The option `id` as written on the command line, like `--preset` or `-f`.
+fn flag(command: &Command, id: &str) -> String {
+    let arg = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .expect("the ids of EXCLUSIVE are arguments");
+    match (arg.get_long(), arg.get_short()) {
+        (Some(long), _) => format!("--{long}"),
+        (None, Some(short)) => format!("-{short}"),
+        (None, None) => id.to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// the environment is shared by the tests, only one of them sets it at a time
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Parse `args` with the variables of `env` set, and none of the other `WAYSIP_*` ones of the
+    /// other tests.
+    pub(crate) fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Cli, clap::Error> {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, value) in env {
+            // SAFETY: the tests reading the environment hold the lock
+            unsafe { std::env::set_var(name, value) };
+        }
+        let cli =
+            Cli::try_parse_layered_from(std::iter::once("waysip").chain(args.iter().copied()));
+        for (name, _) in env {
+            // SAFETY: as above
+            unsafe { std::env::remove_var(name) };
+        }
+        cli
+    }
+
+    fn conflict(result: Result<Cli, clap::Error>) -> bool {
+        matches!(result, Err(e) if e.kind() == ErrorKind::ArgumentConflict)
+    }
+
+    #[test]
+    fn modes_win_over_the_aspect_ratio_of_the_environment() {
+        let env = [("WAYSIP_ASPECT_RATIO", "16:9")];
+        for mode in ["-p", "-i", "-o", "-r"] {
+            let cli = parse(&[mode], &env).unwrap();
+            assert_eq!(cli.aspect_ratio, None, "{mode}");
+        }
+        let cli = parse(&["-d"], &env).unwrap();
+        assert_eq!(cli.aspect_ratio.as_deref(), Some("16:9"));
+        assert!(conflict(parse(&["-p", "-a", "16:9"], &[])));
+    }
+
+    #[test]
+    fn output_options_of_the_command_line_win_over_the_environment() {
+        let cli = parse(&["--preset", "grim"], &[("WAYSIP_FORMAT", "%x\n")]).unwrap();
+        assert_eq!((cli.format, cli.preset.as_deref()), (None, Some("grim")));
+
+        let cli = parse(&["-f", "%w"], &[("WAYSIP_PRESET", "grim")]).unwrap();
+        assert_eq!((cli.format.as_deref(), cli.preset), (Some("%w"), None));
+
+        let cli = parse(&["-f", "%w"], &[("WAYSIP_OUTPUT_FORMAT", "json")]).unwrap();
+        assert!(cli.output_format.is_none());
+        let cli = parse(&["--output-format", "csv"], &[("WAYSIP_FORMAT", "%x\n")]).unwrap();
+        assert!(matches!(cli.output_format, Some(OutputFormat::Csv)));
+        assert_eq!(cli.format, None);
+
+        // from the same place, they still conflict
+        assert!(conflict(parse(&["-f", "%w", "--preset", "grim"], &[])));
+        assert!(conflict(parse(
+            &[],
+            &[("WAYSIP_FORMAT", "%x\n"), ("WAYSIP_OUTPUT_FORMAT", "json")]
+        )));
+    }
+
+    #[test]
+    fn no_label_is_accepted_with_a_label_in_the_environment() {
+        let cli = parse(&["--no-label"], &[("WAYSIP_LABEL", "%wx%h")]).unwrap();
+        assert!(cli.no_label);
+        let cli = parse(&[], &[("WAYSIP_LABEL", "%wx%h")]).unwrap();
+        assert_eq!(cli.label.as_deref(), Some("%wx%h"));
+    }
+
+    #[test]
+    fn toggles_take_an_optional_value() {
+        let cli = parse(
+            &["--crosshair", "--rulers=false", "--status-line=1"],
+            &[("WAYSIP_MARCHING_ANTS", "off")],
+        )
+        .unwrap();
+        assert_eq!(
+            [
+                cli.crosshair,
+                cli.rulers,
+                cli.status_line,
+                cli.marching_ants
+            ],
+            [Some(true), Some(false), Some(true), Some(false)]
+        );
+        assert_eq!((cli.blackout, cli.outline_output), (None, None));
+
+        let cli = parse(&["--outline-output=no"], &[("WAYSIP_OUTLINE_OUTPUT", "1")]).unwrap();
+        assert_eq!(cli.outline_output, Some(false));
+        let cli = parse(&[], &[("WAYSIP_OUTLINE_OUTPUT", "yes")]).unwrap();
+        assert_eq!(cli.outline_output, Some(true));
+
+        // the value is only taken after `=`
+        assert!(parse(&["--crosshair", "true"], &[]).is_err());
+        assert!(parse(&["--rulers=maybe"], &[]).is_err());
+    }
+
+    #[test]
+    fn blackout_and_inactive_color_of_the_command_line_win_over_the_environment() {
+        let cli = parse(&["--blackout"], &[("WAYSIP_INACTIVE_COLOR", "#00000080")]).unwrap();
+        assert_eq!((cli.blackout, cli.inactive_color), (Some(true), None));
+
+        let cli = parse(
+            &["--inactive-color", "#ff000080"],
+            &[("WAYSIP_BLACKOUT", "1")],
+        )
+        .unwrap();
+        assert_eq!(cli.blackout, None);
+        assert_eq!(cli.inactive_color.as_deref(), Some("#ff000080"));
+
+        // turned off, the blackout replaces nothing
+        let env = [
+            ("WAYSIP_BLACKOUT", "0"),
+            ("WAYSIP_INACTIVE_COLOR", "#00000080"),
+        ];
+        let cli = parse(&[], &env).unwrap();
+        assert_eq!(cli.blackout, Some(false));
+        assert_eq!(cli.inactive_color.as_deref(), Some("#00000080"));
+
+        assert!(conflict(parse(
+            &["--blackout", "--inactive-color", "#000000ff"],
+            &[]
+        )));
+        assert!(!conflict(parse(
+            &["--blackout=false", "--inactive-color", "#000000ff"],
+            &[]
+        )));
+    }
+}
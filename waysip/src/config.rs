@@ -0,0 +1,396 @@
+//! User configuration read from `$XDG_CONFIG_HOME/waysip/config.toml`.
+//!
+//! The top level holds defaults for the CLI options, `[profiles.<name>]` tables hold the same
+//! options for `--profile`, `[presets]` extra `--preset` templates, and `[outputs.<name>]` the
+//! style of single outputs:
+//!
+//! ```toml
+//! background = "#00000080"
+//! font_size = 14
+//!
+//! [profiles.record]
+//! preset = "wf-recorder"
+//! aspect_ratio = "16:9"
+//! aspect_presets = ["free", "16:9", "4:3", "1:1", "9:16"]
+//!
+//! [presets]
+//! swayimg = "%x %y %w %h\n"
+//!
+//! [outputs.DP-1]
+//! background = "#000000ff"
+//! ```
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{
+    BorderPosition, BoxPolicy, CardPosition, Cli, Guide, LabelCorner, OutputFormat, StampAnchor,
+};
+
+/// The CLI options that can be set from the config file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_hover_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_policy: Option<BoxPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clamp_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inactive_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blackout: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline_output: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_weight: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_dash: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner_radius: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marching_ants: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_position: Option<BorderPosition>,
+    /// An empty label hides it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_corner: Option<LabelCorner>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen_card: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen_card_position: Option<CardPosition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crosshair: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rulers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guides: Option<Vec<Guide>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_line: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect_presets: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stamp_anchor: Option<StampAnchor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+    /// Ignored when built without the `logger` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+}
+
+impl Options {
+    /// Set the options `args` is lacking.
+    fn fill(&self, args: &mut Cli) {
+        fill(&mut args.background, &self.background);
+        fill(&mut args.border_color, &self.border_color);
+        fill(&mut args.selection_color, &self.selection_color);
+        fill(&mut args.box_color, &self.box_color);
+        fill(&mut args.box_hover_color, &self.box_hover_color);
+        fill(&mut args.box_policy, &self.box_policy);
+        fill(&mut args.clamp_color, &self.clamp_color);
+        // `--blackout` replaces the inactive color, so whichever is set first wins
+        if args.blackout != Some(true) {
+            fill(&mut args.inactive_color, &self.inactive_color);
+        }
+        if args.inactive_color.is_none() {
+            fill(&mut args.blackout, &self.blackout);
+        }
+        fill(&mut args.highlight_color, &self.highlight_color);
+        fill(&mut args.outline_output, &self.outline_output);
+        fill(&mut args.font_name, &self.font_name);
+        fill(&mut args.font_size, &self.font_size);
+        fill(
+            &mut args.border_weight,
+            &self.border_weight.map(|weight| weight.to_string()),
+        );
+        fill(&mut args.border_dash, &self.border_dash);
+        fill(&mut args.corner_radius, &self.corner_radius);
+        fill(&mut args.marching_ants, &self.marching_ants);
+        fill(&mut args.border_position, &self.border_position);
+        if !args.no_label {
+            fill(&mut args.label, &self.label);
+        }
+        fill(&mut args.screen_card, &self.screen_card);
+        fill(&mut args.screen_card_position, &self.screen_card_position);
+        fill(&mut args.label_corner, &self.label_corner);
+        fill(&mut args.label_background, &self.label_background);
+        fill(&mut args.crosshair, &self.crosshair);
+        fill(&mut args.rulers, &self.rulers);
+        fill(&mut args.guides, &self.guides);
+        fill(&mut args.status_line, &self.status_line);
+        // the output options replace each other, so they come from the same layer
+        if args.format.is_none() && args.output_format.is_none() && args.preset.is_none() {
+            args.format = self.format.clone();
+            args.output_format = self.output_format;
+            args.preset = self.preset.clone();
+        }
+        fill(&mut args.aspect_ratio, &self.aspect_ratio);
+        fill(&mut args.aspect_presets, &self.aspect_presets);
+        fill(&mut args.stamp_anchor, &self.stamp_anchor);
+        fill(&mut args.min_width, &self.min_width);
+        fill(&mut args.min_height, &self.min_height);
+        fill(&mut args.max_width, &self.max_width);
+        fill(&mut args.max_height, &self.max_height);
+        fill(&mut args.bounds, &self.bounds);
+        fill(&mut args.timeout, &self.timeout);
+        #[cfg(feature = "logger")]
+        if let (None, Some(level)) = (args.log_level, &self.log_level) {
+            args.log_level = Some(level.parse().unwrap_or_else(|_| {
+                eprintln!("Err: invalid log_level `{level}` in the config file");
+                std::process::exit(1);
+            }));
+        }
+    }
+}
+
+fn fill<T: Clone>(arg: &mut Option<T>, value: &Option<T>) {
+    if arg.is_none() {
+        *arg = value.clone();
+    }
+}
+
+/// The style of a single output, over the one of the other outputs.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct OutputStyle {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inactive_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_weight: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// Defaults for every invocation.
+    pub options: Options,
+    /// Named sets of options, selected with `--profile`.
+    pub profiles: BTreeMap<String, Options>,
+    /// Extra `--preset` templates, keyed by name. They take precedence over the built-in ones.
+    pub presets: BTreeMap<String, String>,
+    /// Styles of single outputs, keyed by output name.
+    pub outputs: BTreeMap<String, OutputStyle>,
+}
+
+impl Config {
+    fn from_str(content: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        // the remaining top level keys are options, so that unknown ones are reported
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            None => BTreeMap::new(),
+        };
+        let presets = match table.remove("presets") {
+            Some(presets) => presets.try_into()?,
+            None => BTreeMap::new(),
+        };
+        let outputs = match table.remove("outputs") {
+            Some(outputs) => outputs.try_into()?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self {
+            options: table.try_into()?,
+            profiles,
+            presets,
+            outputs,
+        })
+    }
+}
+
+impl Config {
+    /// Location of the configuration file, if a config directory can be found.
+    pub(crate) fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("waysip").join("config.toml"))
+    }
+
+    /// Read the configuration file. A missing file gives the default configuration, an invalid
+    /// one is a fatal error.
+    pub(crate) fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("Err: cannot read {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        };
+        Self::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Err: invalid config {}: {e}", path.display());
+            std::process::exit(1);
+        })
+    }
+
+    /// Complete the options missing from the command line and the environment with the ones of
+    /// the selected profile, then with the top level ones.
+    pub(crate) fn apply(&self, args: &mut Cli) {
+        if let Some(name) = &args.profile {
+            let Some(profile) = self.profiles.get(name) else {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                eprintln!(
+                    "Unknown profile `{name}`, available profiles: {}",
+                    names.join(", ")
+                );
+                std::process::exit(1);
+            };
+            profile.fill(args);
+        }
+        self.options.fill(args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tests::parse;
+
+    const CONFIG: &str = r##"
+background = "#111111ff"
+font_size = 10
+crosshair = true
+label = "%wx%h"
+format = "%x %y\n"
+inactive_color = "#00000080"
+
+[profiles.dark]
+background = "#222222ff"
+crosshair = false
+preset = "grim"
+blackout = true
+
+[profiles.json]
+output_format = "json"
+
+[profiles.empty]
+"##;
+
+    fn applied(args: &[&str]) -> Cli {
+        let config = Config::from_str(CONFIG).unwrap();
+        let mut cli = parse(args, &[]).unwrap();
+        config.apply(&mut cli);
+        cli
+    }
+
+    #[test]
+    fn profile_over_top_level() {
+        let cli = applied(&[]);
+        assert_eq!(cli.background.as_deref(), Some("#111111ff"));
+        assert_eq!((cli.font_size, cli.crosshair), (Some(10), Some(true)));
+
+        let cli = applied(&["--profile", "dark"]);
+        assert_eq!(cli.background.as_deref(), Some("#222222ff"));
+        // the first layer setting an option wins, also to turn it off
+        assert_eq!((cli.font_size, cli.crosshair), (Some(10), Some(false)));
+
+        let cli = applied(&["--profile", "empty"]);
+        assert_eq!(cli.background.as_deref(), Some("#111111ff"));
+    }
+
+    #[test]
+    fn command_line_over_profile() {
+        let cli = applied(&["--profile", "dark", "-b", "#333333ff", "--crosshair"]);
+        assert_eq!(cli.background.as_deref(), Some("#333333ff"));
+        assert_eq!(cli.crosshair, Some(true));
+        let cli = applied(&["--no-label"]);
+        assert_eq!(cli.label, None);
+    }
+
+    #[test]
+    fn output_options_come_from_one_layer() {
+        let cli = applied(&[]);
+        assert_eq!(cli.format.as_deref(), Some("%x %y\n"));
+        assert_eq!(cli.preset, None);
+
+        // a profile setting one of them replaces all of them
+        let cli = applied(&["--profile", "dark"]);
+        assert_eq!((cli.format, cli.preset.as_deref()), (None, Some("grim")));
+        let cli = applied(&["--profile", "json"]);
+        assert!(matches!(cli.output_format, Some(OutputFormat::Json)));
+        assert_eq!((cli.format, cli.preset), (None, None));
+
+        // as does the command line
+        let cli = applied(&["--profile", "dark", "--output-format", "csv"]);
+        assert!(matches!(cli.output_format, Some(OutputFormat::Csv)));
+        assert_eq!((cli.format, cli.preset), (None, None));
+    }
+
+    #[test]
+    fn blackout_and_inactive_color_come_from_one_layer() {
+        let cli = applied(&[]);
+        assert_eq!(cli.inactive_color.as_deref(), Some("#00000080"));
+        assert_eq!(cli.blackout, None);
+        let cli = applied(&["--profile", "dark"]);
+        assert_eq!((cli.inactive_color, cli.blackout), (None, Some(true)));
+        let cli = applied(&["--profile", "dark", "--inactive-color", "#ff000080"]);
+        assert_eq!(cli.inactive_color.as_deref(), Some("#ff000080"));
+        assert_eq!(cli.blackout, None);
+        // turned off, the blackout lets the inactive color of a lower layer through
+        let cli = applied(&["--profile", "dark", "--blackout=false"]);
+        assert_eq!(cli.inactive_color.as_deref(), Some("#00000080"));
+        assert_eq!(cli.blackout, Some(false));
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        assert!(Config::from_str("no_such_option = 1").is_err());
+        assert!(Config::from_str("[profiles.p]\nno_such_option = 1").is_err());
+        assert!(Config::from_str("font_size = \"big\"").is_err());
+    }
+}
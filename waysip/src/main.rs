@@ -1,17 +1,27 @@
 mod cli;
+mod config;
 #[cfg(feature = "logger")]
 mod logger;
+#[cfg(feature = "providers")]
+mod providers;
+mod record;
 mod settings;
 mod utils;
 
-use clap::Parser;
 use cli::Cli;
-use libwaysip::SelectionType;
-use settings::{SelectionDispatch, read_boxes_from_stdin, resolve_output_format, run_selection};
-use utils::apply_format;
+use config::Config;
+use libwaysip::{
+    AreaInfo, SelectionType,
+    format::{AreaFields, Template},
+};
+use record::format_record;
+use settings::{
+    OverlayTexts, SelectionDispatch, effective_config, read_boxes, resolve_output_format,
+    run_identify, run_preview, run_selection,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = Cli::parse();
+    let mut args = Cli::parse_layered();
 
     #[cfg(feature = "completions")]
     if let Some(shell) = args.completions {
@@ -19,25 +29,72 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    let config = Config::load();
+    config.apply(&mut args);
+
     #[cfg(feature = "logger")]
     logger::setup(&args);
 
-    let fmt = resolve_output_format(&mut args);
+    if args.print_config {
+        print!("{}", effective_config(&args, &config));
+        return Ok(());
+    }
+
+    if let Some(path) = args.preview.take() {
+        run_preview(&mut args, &path, &config.outputs);
+        return Ok(());
+    }
+
+    if args.identify {
+        run_identify(&mut args, &config.outputs);
+        return Ok(());
+    }
+
+    let fmt = resolve_output_format(&mut args, &config);
+    // report format errors before the overlay shows up
+    let template = Template::parse(&fmt).unwrap_or_else(|e| {
+        eprintln!("Err: {e}");
+        std::process::exit(1);
+    });
+    let texts = OverlayTexts::from_cli(&args);
+    let output_format = args.output_format;
+    let print_info =
+        |info: &AreaInfo, selection_type: SelectionType, screen: bool| match output_format {
+            Some(format) => print!("{}", format_record(info, format, selection_type, screen)),
+            None => print!("{}", template.render(&AreaFields::new(info, screen))),
+        };
 
-    if args.boxes {
-        let boxes = read_boxes_from_stdin();
-        let info = run_selection(&mut args, SelectionType::PredefinedBoxes, Some(boxes));
-        print!("{}", apply_format(&info, &fmt, false));
+    if let Some(boxes) = read_boxes(&args) {
+        let selection_type = match args.dimensions {
+            true => SelectionType::BoxesOrDimensions,
+            false => SelectionType::PredefinedBoxes,
+        };
+        let info = run_selection(
+            &mut args,
+            selection_type,
+            Some(boxes),
+            texts,
+            &config.outputs,
+        );
+        print_info(&info, selection_type, false);
     } else if let Some(mode) = SelectionDispatch::from_cli(&args) {
-        let info = run_selection(&mut args, mode.selection_type(), None);
+        let info = run_selection(
+            &mut args,
+            mode.selection_type(),
+            None,
+            texts,
+            &config.outputs,
+        );
         let use_screen_format = match mode {
             SelectionDispatch::DimensionsOrOutput => {
                 matches!(info.effective_selection_type, Some(SelectionType::Screen))
             }
             SelectionDispatch::Screen => true,
-            SelectionDispatch::Point | SelectionDispatch::Area => false,
+            SelectionDispatch::Point
+            | SelectionDispatch::Area
+            | SelectionDispatch::FixedSize(..) => false,
         };
-        print!("{}", apply_format(&info, &fmt, use_screen_format));
+        print_info(&info, mode.selection_type(), use_screen_format);
     }
 
     Ok(())
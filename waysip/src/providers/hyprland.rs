@@ -0,0 +1,173 @@
+//! The IPC of Hyprland: one request per connection on the `.socket.sock` of the instance.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use libwaysip::BoxInfo;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use super::{BoxProvider, ProviderError, window_box};
+
+pub(crate) struct Hyprland {
+    socket: PathBuf,
+}
+
+impl Hyprland {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// The socket of `$HYPRLAND_INSTANCE_SIGNATURE`, in `$XDG_RUNTIME_DIR/hypr`, or in `/tmp/hypr`
+    /// for the older releases.
+    pub fn from_env() -> Option<Self> {
+        let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| Path::new(&dir).join("hypr"))
+            .filter(|dir| dir.exists())
+            .unwrap_or_else(|| PathBuf::from("/tmp/hypr"));
+        Some(Self::new(runtime.join(signature).join(".socket.sock")))
+    }
+
+    /// Send the JSON flavour of `command` and read the reply, until the socket is closed.
+    fn request<T: DeserializeOwned>(&self, command: &str) -> Result<T, ProviderError> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(format!("j/{command}").as_bytes())?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        // the errors come as plain text
+        if !reply.trim_start().starts_with(['[', '{']) {
+            return Err(ProviderError::Refused(reply.trim().to_string()));
+        }
+        Ok(serde_json::from_str(&reply)?)
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkspaceRef {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Monitor {
+    active_workspace: WorkspaceRef,
+    special_workspace: Option<WorkspaceRef>,
+}
+
+#[derive(Deserialize)]
+struct Client {
+    at: [f64; 2],
+    size: [f64; 2],
+    title: String,
+    workspace: WorkspaceRef,
+    #[serde(default = "default_true")]
+    mapped: bool,
+    #[serde(default)]
+    hidden: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl BoxProvider for Hyprland {
+    fn windows(&self) -> Result<Vec<BoxInfo>, ProviderError> {
+        let monitors: Vec<Monitor> = self.request("monitors")?;
+        let shown: HashSet<i64> = monitors
+            .iter()
+            .flat_map(|monitor| {
+                // the id of the special workspace is 0 when none is open
+                let special = monitor
+                    .special_workspace
+                    .as_ref()
+                    .map(|workspace| workspace.id)
+                    .filter(|id| *id != 0);
+                std::iter::once(monitor.active_workspace.id).chain(special)
+            })
+            .collect();
+        let clients: Vec<Client> = self.request("clients")?;
+        Ok(clients
+            .into_iter()
+            .filter(|client| client.mapped && !client.hidden)
+            .filter(|client| shown.contains(&client.workspace.id))
+            .map(|client| {
+                let [x, y] = client.at;
+                let [width, height] = client.size;
+                window_box(x, y, width, height, Some(client.title))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::providers::tests::{Server, summary};
+
+    fn client(title: &str, workspace: i64, extra: serde_json::Value) -> serde_json::Value {
+        let mut client = json!({
+            "at": [10, 20],
+            "size": [300, 200],
+            "title": title,
+            "workspace": {"id": workspace, "name": workspace.to_string()},
+        });
+        client
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+    }
+
+    #[test]
+    fn windows_of_the_active_and_open_special_workspaces() {
+        let monitors = json!([
+            {"name": "DP-1", "activeWorkspace": {"id": 1}, "specialWorkspace": {"id": 0}},
+            {"name": "DP-2", "activeWorkspace": {"id": 3}, "specialWorkspace": {"id": -98}},
+        ]);
+        let clients = json!([
+            client("firefox", 1, json!({"at": [0, 0], "size": [1920, 1080]})),
+            client("other workspace", 2, json!({})),
+            client("open special", -98, json!({"at": [1930, 10]})),
+            client("closed special", -99, json!({})),
+            client("hidden in a group", 1, json!({"hidden": true})),
+            client("unmapped", 3, json!({"mapped": false})),
+            client("", 3, json!({"at": [1920, 0]})),
+        ]);
+        let server = Server::new(vec![
+            monitors.to_string().into_bytes(),
+            clients.to_string().into_bytes(),
+        ]);
+        let boxes = Hyprland::new(&server.socket).windows().unwrap();
+        assert_eq!(
+            summary(&boxes),
+            [
+                ([0.0, 0.0, 1920.0, 1080.0], Some("firefox")),
+                ([1930.0, 10.0, 300.0, 200.0], Some("open special")),
+                ([1920.0, 0.0, 300.0, 200.0], None),
+            ]
+        );
+        assert_eq!(
+            server.requests(),
+            [b"j/monitors".to_vec(), b"j/clients".to_vec()]
+        );
+    }
+
+    #[test]
+    fn refused_with_a_plain_text_reply() {
+        let server = Server::new(vec![b"unknown request\n".to_vec()]);
+        let error = Hyprland::new(&server.socket).windows().unwrap_err();
+        assert!(
+            matches!(&error, ProviderError::Refused(message) if message == "unknown request"),
+            "{error}"
+        );
+        server.requests();
+    }
+}
@@ -0,0 +1,125 @@
+//! Predefined boxes listed from the IPC of the compositors, for `--windows`.
+
+mod hyprland;
+mod niri;
+mod sway;
+
+use libwaysip::BoxInfo;
+use thiserror::Error;
+
+pub(crate) use hyprland::Hyprland;
+pub(crate) use niri::Niri;
+pub(crate) use sway::Sway;
+
+#[derive(Debug, Error)]
+pub(crate) enum ProviderError {
+    #[error("cannot talk to the compositor: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid reply from the compositor: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("the compositor refused the request: {0}")]
+    Refused(String),
+}
+
+/// A source of predefined boxes.
+pub(crate) trait BoxProvider {
+    /// The visible windows, labelled with their title.
+    fn windows(&self) -> Result<Vec<BoxInfo>, ProviderError>;
+}
+
+/// The provider of the running compositor, found from the socket it exports.
+pub(crate) fn detect() -> Option<Box<dyn BoxProvider>> {
+    if let Some(niri) = Niri::from_env() {
+        return Some(Box::new(niri));
+    }
+    if let Some(hyprland) = Hyprland::from_env() {
+        return Some(Box::new(hyprland));
+    }
+    Sway::from_env().map(|sway| Box::new(sway) as Box<dyn BoxProvider>)
+}
+
+/// The boxes of `--windows`.
+pub(crate) fn list_windows() -> Vec<BoxInfo> {
+    let Some(provider) = detect() else {
+        eprintln!("Err: --windows needs sway, i3, Hyprland or niri, none of their sockets is set");
+        std::process::exit(1);
+    };
+    provider.windows().unwrap_or_else(|e| {
+        eprintln!("Err: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// A window at `x, y` of `width x height`, in the layout coordinates.
+fn window_box(x: f64, y: f64, width: f64, height: f64, title: Option<String>) -> BoxInfo {
+    let mut info = BoxInfo::new(x, y, x + width, y + height);
+    info.label = title.filter(|title| !title.is_empty());
+    info
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixListener,
+        path::PathBuf,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// A compositor socket in a temporary directory, answering each connection with the next of
+    /// its canned replies.
+    pub(crate) struct Server {
+        _dir: tempfile::TempDir,
+        pub socket: PathBuf,
+        thread: JoinHandle<Vec<Vec<u8>>>,
+    }
+
+    impl Server {
+        pub fn new(replies: Vec<Vec<u8>>) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let socket = dir.path().join("ipc.sock");
+            let listener = UnixListener::bind(&socket).unwrap();
+            let thread = std::thread::spawn(move || {
+                replies
+                    .into_iter()
+                    .map(|reply| {
+                        let (mut stream, _) = listener.accept().unwrap();
+                        let mut request = vec![0; 1024];
+                        let read = stream.read(&mut request).unwrap();
+                        request.truncate(read);
+                        stream.write_all(&reply).unwrap();
+                        request
+                    })
+                    .collect()
+            });
+            Self {
+                _dir: dir,
+                socket,
+                thread,
+            }
+        }
+
+        /// The requests received, once every reply is sent.
+        pub fn requests(self) -> Vec<Vec<u8>> {
+            self.thread.join().unwrap()
+        }
+    }
+
+    /// The boxes as `[x, y, width, height]` and their label.
+    pub(crate) fn summary(boxes: &[BoxInfo]) -> Vec<([f64; 4], Option<&str>)> {
+        boxes
+            .iter()
+            .map(|info| {
+                let rect = [
+                    info.start_x,
+                    info.start_y,
+                    info.end_x - info.start_x,
+                    info.end_y - info.start_y,
+                ];
+                (rect, info.label.as_deref())
+            })
+            .collect()
+    }
+}
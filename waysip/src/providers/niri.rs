@@ -0,0 +1,240 @@
+//! The IPC of niri: a JSON request and a JSON reply, one line each, on `$NIRI_SOCKET`.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use libwaysip::BoxInfo;
+use serde::Deserialize;
+
+use super::{BoxProvider, ProviderError, window_box};
+
+pub(crate) struct Niri {
+    socket: PathBuf,
+}
+
+impl Niri {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os("NIRI_SOCKET").map(Self::new)
+    }
+
+    /// Send a request without arguments, like `"Windows"`, and read the reply.
+    fn request(&self, request: &str) -> Result<Response, ProviderError> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(format!("\"{request}\"\n").as_bytes())?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        match serde_json::from_str(&reply)? {
+            Reply::Ok(response) => Ok(response),
+            Reply::Err(message) => Err(ProviderError::Refused(message)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum Reply {
+    Ok(Response),
+    Err(String),
+}
+
+#[derive(Deserialize)]
+enum Response {
+    Outputs(HashMap<String, Output>),
+    Workspaces(Vec<Workspace>),
+    Windows(Vec<Window>),
+}
+
+#[derive(Deserialize)]
+struct Output {
+    /// `None` for the disabled outputs
+    logical: Option<LogicalOutput>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct LogicalOutput {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    id: u64,
+    output: Option<String>,
+    is_active: bool,
+}
+
+#[derive(Deserialize)]
+struct Window {
+    title: Option<String>,
+    workspace_id: Option<u64>,
+    /// missing from the older releases
+    layout: Option<WindowLayout>,
+}
+
+#[derive(Deserialize)]
+struct WindowLayout {
+    /// relative to the output, `None` when the window is not in the view
+    tile_pos_in_workspace_view: Option<[f64; 2]>,
+    window_offset_in_tile: [f64; 2],
+    window_size: [f64; 2],
+}
+
+impl BoxProvider for Niri {
+    fn windows(&self) -> Result<Vec<BoxInfo>, ProviderError> {
+        let Response::Outputs(outputs) = self.request("Outputs")? else {
+            return Err(ProviderError::Refused(
+                "unexpected reply to Outputs".to_string(),
+            ));
+        };
+        let Response::Workspaces(workspaces) = self.request("Workspaces")? else {
+            return Err(ProviderError::Refused(
+                "unexpected reply to Workspaces".to_string(),
+            ));
+        };
+        let Response::Windows(windows) = self.request("Windows")? else {
+            return Err(ProviderError::Refused(
+                "unexpected reply to Windows".to_string(),
+            ));
+        };
+        // the output of each active workspace
+        let shown: HashMap<u64, LogicalOutput> = workspaces
+            .into_iter()
+            .filter(|workspace| workspace.is_active)
+            .filter_map(|workspace| {
+                let output = outputs.get(workspace.output.as_ref()?)?.logical?;
+                Some((workspace.id, output))
+            })
+            .collect();
+        Ok(windows
+            .into_iter()
+            .filter_map(|window| {
+                let output = shown.get(&window.workspace_id?)?;
+                let layout = window.layout?;
+                let [tile_x, tile_y] = layout.tile_pos_in_workspace_view?;
+                let [offset_x, offset_y] = layout.window_offset_in_tile;
+                let [width, height] = layout.window_size;
+                let x = output.x + tile_x + offset_x;
+                let y = output.y + tile_y + offset_y;
+                // the columns scrolled out of the view
+                let in_view = x < output.x + output.width
+                    && x + width > output.x
+                    && y < output.y + output.height
+                    && y + height > output.y;
+                in_view.then(|| window_box(x, y, width, height, window.title))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::providers::tests::{Server, summary};
+
+    fn line(reply: serde_json::Value) -> Vec<u8> {
+        format!("{reply}\n").into_bytes()
+    }
+
+    fn window(
+        title: &str,
+        workspace: u64,
+        tile: Option<[f64; 2]>,
+        size: [f64; 2],
+    ) -> serde_json::Value {
+        json!({
+            "id": 1,
+            "title": title,
+            "workspace_id": workspace,
+            "layout": {
+                "tile_pos_in_workspace_view": tile,
+                "window_offset_in_tile": [5.0, 6.0],
+                "window_size": size,
+            },
+        })
+    }
+
+    fn replies(windows: serde_json::Value) -> Vec<Vec<u8>> {
+        let logical = json!({"x": 1920, "y": 0, "width": 1280, "height": 720, "scale": 1.5});
+        vec![
+            line(json!({"Ok": {"Outputs": {
+                "DP-1": {"name": "DP-1", "logical": logical},
+                "HDMI-A-1": {"name": "HDMI-A-1", "logical": null},
+            }}})),
+            line(json!({"Ok": {"Workspaces": [
+                {"id": 1, "output": "DP-1", "is_active": true},
+                {"id": 2, "output": "DP-1", "is_active": false},
+                {"id": 3, "output": "HDMI-A-1", "is_active": true},
+                {"id": 4, "output": null, "is_active": false},
+            ]}})),
+            line(json!({"Ok": {"Windows": windows}})),
+        ]
+    }
+
+    #[test]
+    fn windows_in_the_view_of_the_active_workspaces() {
+        let mut no_layout = window("no layout", 1, Some([0.0, 0.0]), [10.0, 10.0]);
+        no_layout.as_object_mut().unwrap().remove("layout");
+        let mut no_workspace = window("no workspace", 1, Some([0.0, 0.0]), [10.0, 10.0]);
+        no_workspace["workspace_id"] = json!(null);
+        let windows = json!([
+            window("shown", 1, Some([10.0, 20.0]), [600.0, 400.0]),
+            window("inactive workspace", 2, Some([0.0, 0.0]), [600.0, 400.0]),
+            window("disabled output", 3, Some([0.0, 0.0]), [600.0, 400.0]),
+            window("out of the view", 1, None, [600.0, 400.0]),
+            window("scrolled out", 1, Some([1300.0, 0.0]), [600.0, 400.0]),
+            window("scrolled left", 1, Some([-700.0, 0.0]), [600.0, 400.0]),
+            window("partly shown", 1, Some([1000.0, 0.0]), [600.0, 400.0]),
+            no_layout,
+            no_workspace,
+        ]);
+        let server = Server::new(replies(windows));
+        let boxes = Niri::new(&server.socket).windows().unwrap();
+        assert_eq!(
+            summary(&boxes),
+            [
+                ([1935.0, 26.0, 600.0, 400.0], Some("shown")),
+                ([2925.0, 6.0, 600.0, 400.0], Some("partly shown")),
+            ]
+        );
+        assert_eq!(
+            server.requests(),
+            [
+                b"\"Outputs\"\n".to_vec(),
+                b"\"Workspaces\"\n".to_vec(),
+                b"\"Windows\"\n".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn refused_with_an_error_reply() {
+        let server = Server::new(vec![line(json!({"Err": "the compositor said no"}))]);
+        let error = Niri::new(&server.socket).windows().unwrap_err();
+        assert!(
+            matches!(&error, ProviderError::Refused(message) if message == "the compositor said no"),
+            "{error}"
+        );
+        server.requests();
+    }
+
+    #[test]
+    fn refused_with_an_unexpected_response() {
+        let server = Server::new(vec![line(json!({"Ok": {"Windows": []}}))]);
+        let error = Niri::new(&server.socket).windows().unwrap_err();
+        assert!(matches!(error, ProviderError::Refused(_)), "{error}");
+        server.requests();
+    }
+}
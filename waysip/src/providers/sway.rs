@@ -0,0 +1,253 @@
+//! The IPC of sway and i3: binary framed JSON messages on `$SWAYSOCK` or `$I3SOCK`.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use libwaysip::BoxInfo;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use super::{BoxProvider, ProviderError, window_box};
+
+const MAGIC: &[u8] = b"i3-ipc";
+const GET_WORKSPACES: u32 = 1;
+const GET_TREE: u32 = 4;
+
+pub(crate) struct Sway {
+    socket: PathBuf,
+}
+
+impl Sway {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os("SWAYSOCK")
+            .or_else(|| std::env::var_os("I3SOCK"))
+            .map(Self::new)
+    }
+
+    /// Send a message of type `kind` without payload and read the reply.
+    fn request<T: DeserializeOwned>(&self, kind: u32) -> Result<T, ProviderError> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        let mut message = MAGIC.to_vec();
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        stream.write_all(&message)?;
+
+        let mut header = [0; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(ProviderError::Refused("not an i3 IPC reply".to_string()));
+        }
+        let length = u32::from_ne_bytes(header[6..10].try_into().expect("4 bytes"));
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    name: String,
+    visible: bool,
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    rect: Rect,
+    /// sway only, false for the windows hidden in tabbed or stacked containers
+    visible: Option<bool>,
+    /// sway windows
+    pid: Option<i64>,
+    /// X11 windows
+    window: Option<i64>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    floating_nodes: Vec<Node>,
+}
+
+impl BoxProvider for Sway {
+    fn windows(&self) -> Result<Vec<BoxInfo>, ProviderError> {
+        let workspaces: Vec<Workspace> = self.request(GET_WORKSPACES)?;
+        let visible: HashSet<String> = workspaces
+            .into_iter()
+            .filter(|workspace| workspace.visible)
+            .map(|workspace| workspace.name)
+            .collect();
+        let tree: Node = self.request(GET_TREE)?;
+        let mut boxes = Vec::new();
+        collect_windows(tree, &visible, false, &mut boxes);
+        Ok(boxes)
+    }
+}
+
+/// Add the windows of `node` to `boxes`, the floating ones after the tiled ones as they are above.
+fn collect_windows(
+    node: Node,
+    visible: &HashSet<String>,
+    in_visible_workspace: bool,
+    boxes: &mut Vec<BoxInfo>,
+) {
+    let in_visible_workspace = match node.kind.as_str() {
+        "workspace" => node
+            .name
+            .as_ref()
+            .is_some_and(|name| visible.contains(name)),
+        _ => in_visible_workspace,
+    };
+    let is_window = node.pid.is_some() || node.window.is_some();
+    if is_window && in_visible_workspace && node.visible != Some(false) {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = node.rect;
+        boxes.push(window_box(x, y, width, height, node.name));
+        return;
+    }
+    for child in node.nodes.into_iter().chain(node.floating_nodes) {
+        collect_windows(child, visible, in_visible_workspace, boxes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::providers::tests::{Server, summary};
+
+    fn frame(kind: u32, payload: &serde_json::Value) -> Vec<u8> {
+        let payload = payload.to_string();
+        let mut message = MAGIC.to_vec();
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        message
+    }
+
+    fn window(name: &str, rect: [f64; 4], extra: serde_json::Value) -> serde_json::Value {
+        let mut node = json!({
+            "type": "con",
+            "name": name,
+            "rect": {"x": rect[0], "y": rect[1], "width": rect[2], "height": rect[3]},
+            "pid": 42,
+        });
+        node.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        node
+    }
+
+    fn workspace(name: &str, nodes: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "type": "workspace",
+            "name": name,
+            "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+            "nodes": nodes,
+        })
+    }
+
+    #[test]
+    fn visible_windows_of_the_visible_workspaces() {
+        let workspaces = json!([
+            {"name": "1", "visible": true},
+            {"name": "2", "visible": false},
+            {"name": "__i3_scratch", "visible": false},
+        ]);
+        let tabbed = json!({
+            "type": "con",
+            "name": null,
+            "rect": {"x": 0, "y": 0, "width": 960, "height": 1080},
+            "layout": "tabbed",
+            "nodes": [
+                window("shown tab", [0.0, 30.0, 960.0, 1050.0], json!({"visible": true})),
+                window("hidden tab", [0.0, 30.0, 960.0, 1050.0], json!({"visible": false})),
+            ],
+        });
+        let mut current = workspace(
+            "1",
+            vec![
+                tabbed,
+                window("kitty", [960.0, 0.0, 960.0, 1080.0], json!({})),
+                // i3 has no `visible`, and X11 windows a `window` instead of a `pid`
+                window(
+                    "xterm",
+                    [0.0, 0.0, 10.0, 10.0],
+                    json!({"pid": null, "window": 7}),
+                ),
+            ],
+        );
+        current["floating_nodes"] =
+            json!([window("float", [100.0, 100.0, 300.0, 200.0], json!({}))]);
+        let tree = json!({
+            "type": "root",
+            "name": "root",
+            "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+            "nodes": [
+                {
+                    "type": "output",
+                    "name": "__i3",
+                    "rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+                    "nodes": [workspace("__i3_scratch", vec![
+                        window("scratchpad", [0.0, 0.0, 500.0, 500.0], json!({})),
+                    ])],
+                },
+                {
+                    "type": "output",
+                    "name": "DP-1",
+                    "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                    "nodes": [
+                        current,
+                        workspace("2", vec![window("other", [0.0, 0.0, 1920.0, 1080.0], json!({}))]),
+                    ],
+                },
+            ],
+        });
+        let server = Server::new(vec![
+            frame(GET_WORKSPACES, &workspaces),
+            frame(GET_TREE, &tree),
+        ]);
+        let boxes = Sway::new(&server.socket).windows().unwrap();
+        assert_eq!(
+            summary(&boxes),
+            [
+                ([0.0, 30.0, 960.0, 1050.0], Some("shown tab")),
+                ([960.0, 0.0, 960.0, 1080.0], Some("kitty")),
+                ([0.0, 0.0, 10.0, 10.0], Some("xterm")),
+                ([100.0, 100.0, 300.0, 200.0], Some("float")),
+            ]
+        );
+        let requests = server.requests();
+        let header = |kind: u32| [MAGIC, &0u32.to_ne_bytes(), &kind.to_ne_bytes()].concat();
+        assert_eq!(requests, [header(GET_WORKSPACES), header(GET_TREE)]);
+    }
+
+    #[test]
+    fn refused_when_the_reply_is_not_i3_ipc() {
+        let server = Server::new(vec![b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec()]);
+        let error = Sway::new(&server.socket).windows().unwrap_err();
+        assert!(matches!(error, ProviderError::Refused(_)), "{error}");
+        server.requests();
+    }
+}
@@ -0,0 +1,326 @@
+//! Structured serialisation of a selection for `--output-format`.
+
+use libwaysip::{
+    AreaInfo, Position, SelectionType, Size, Subpixel,
+    format::{Geometry, transform_name},
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::cli::OutputFormat;
+
+/// Prefix of the variables written by `--output-format shell`.
+const SHELL_PREFIX: &str = "WAYSIP_";
+
+#[derive(Serialize)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Serialize)]
+struct ScreenRecord<'a> {
+    name: &'a str,
+    description: &'a str,
+    make: &'a str,
+    model: &'a str,
+    x: i32,
+    y: i32,
+    /// Logical size.
+    width: i32,
+    height: i32,
+    /// Size of the current mode.
+    mode_width: i32,
+    mode_height: i32,
+    physical_width_mm: i32,
+    physical_height_mm: i32,
+    refresh_hz: f64,
+    preferred_mode: bool,
+    scale: f64,
+    transform: &'static str,
+    subpixel: &'static str,
+    enabled: Option<bool>,
+}
+
+/// Everything known about a selection, in the shape written by the structured formats.
+#[derive(Serialize)]
+struct AreaRecord<'a> {
+    selection_type: &'static str,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    relative: Rect,
+    physical: Rect,
+    /// The label of the selected predefined box.
+    label: Option<&'a str>,
+    screen: ScreenRecord<'a>,
+}
+
+impl<'a> AreaRecord<'a> {
+    fn new(info: &'a AreaInfo, selection_type: SelectionType, screen: bool) -> Self {
+        let Geometry {
+            x,
+            y,
+            width,
+            height,
+            rel_x,
+            rel_y,
+            rel_width,
+            rel_height,
+            phys_x,
+            phys_y,
+            phys_width,
+            phys_height,
+        } = Geometry::new(info, screen);
+
+        let screen_info = info.selected_screen_info();
+        let Position { x: sx, y: sy } = screen_info.get_position();
+        let Size {
+            width: sw,
+            height: sh,
+        } = screen_info.get_size();
+        let Size {
+            width: mode_w,
+            height: mode_h,
+        } = screen_info.get_wloutput_size();
+        let Size {
+            width: mm_w,
+            height: mm_h,
+        } = screen_info.get_physical_size();
+
+        Self {
+            selection_type: selection_type_name(
+                info.effective_selection_type.unwrap_or(selection_type),
+            ),
+            x,
+            y,
+            width,
+            height,
+            relative: Rect {
+                x: rel_x,
+                y: rel_y,
+                width: rel_width,
+                height: rel_height,
+            },
+            physical: Rect {
+                x: phys_x,
+                y: phys_y,
+                width: phys_width,
+                height: phys_height,
+            },
+            label: info.box_info.label.as_deref(),
+            screen: ScreenRecord {
+                name: screen_info.get_name(),
+                description: screen_info.get_description(),
+                make: screen_info.get_make(),
+                model: screen_info.get_model(),
+                x: sx,
+                y: sy,
+                width: sw,
+                height: sh,
+                mode_width: mode_w,
+                mode_height: mode_h,
+                physical_width_mm: mm_w,
+                physical_height_mm: mm_h,
+                refresh_hz: screen_info.get_refresh_rate(),
+                preferred_mode: screen_info.is_preferred_mode(),
+                scale: screen_info.get_scale(),
+                transform: transform_name(screen_info.get_transform()),
+                subpixel: subpixel_name(screen_info.get_subpixel()),
+                enabled: screen_info.is_enabled(),
+            },
+        }
+    }
+}
+
+/// Serialise the selection. `selection_type` is the requested mode, the effective one reported by
+/// [AreaInfo] takes precedence; with `screen` the geometry is the one of the whole output.
+pub(crate) fn format_record(
+    info: &AreaInfo,
+    format: OutputFormat,
+    selection_type: SelectionType,
+    screen: bool,
+) -> String {
+    let record = AreaRecord::new(info, selection_type, screen);
+    let value = serde_json::to_value(&record).expect("AreaRecord is always serialisable");
+    match format {
+        OutputFormat::Json => format!("{value}\n"),
+        OutputFormat::Shell => shell_assignments(&value),
+        OutputFormat::Csv => csv_table(&value),
+    }
+}
+
+/// One `WAYSIP_<KEY>='value'` line per flattened field, to be `eval`ed.
+fn shell_assignments(value: &Value) -> String {
+    flatten(value)
+        .into_iter()
+        .map(|(key, value)| {
+            format!(
+                "{SHELL_PREFIX}{}={}\n",
+                key.to_uppercase(),
+                shell_quote(&scalar_to_string(value))
+            )
+        })
+        .collect()
+}
+
+/// A header line with the flattened keys and a line with their values.
+fn csv_table(value: &Value) -> String {
+    let fields = flatten(value);
+    let header: Vec<String> = fields.iter().map(|(key, _)| csv_quote(key)).collect();
+    let row: Vec<String> = fields
+        .iter()
+        .map(|(_, value)| csv_quote(&scalar_to_string(value)))
+        .collect();
+    format!("{}\n{}\n", header.join(","), row.join(","))
+}
+
+/// Flatten nested objects into `parent_child` keys, keeping the field order.
+fn flatten(value: &Value) -> Vec<(String, &Value)> {
+    fn walk<'v>(prefix: &str, map: &'v Map<String, Value>, out: &mut Vec<(String, &'v Value)>) {
+        for (key, value) in map {
+            let key = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{prefix}_{key}")
+            };
+            match value {
+                Value::Object(map) => walk(&key, map, out),
+                other => out.push((key, other)),
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    if let Value::Object(map) = value {
+        walk("", map, &mut out);
+    }
+    out
+}
+
+/// Scalars as plain text, `null` becomes an empty string.
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Single-quote `s` so that it is safe to `eval`.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn csv_quote(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub(crate) fn selection_type_name(selection_type: SelectionType) -> &'static str {
+    match selection_type {
+        SelectionType::Area => "area",
+        SelectionType::Point => "point",
+        SelectionType::Screen => "screen",
+        SelectionType::PredefinedBoxes => "predefined_boxes",
+        SelectionType::DimensionsOrOutput => "dimensions_or_output",
+        SelectionType::BoxesOrDimensions => "boxes_or_dimensions",
+        SelectionType::FixedSize(..) => "fixed_size",
+        SelectionType::Identify => "identify",
+        _ => "unknown",
+    }
+}
+
+fn subpixel_name(subpixel: Subpixel) -> &'static str {
+    match subpixel {
+        Subpixel::None => "none",
+        Subpixel::HorizontalRgb => "horizontal_rgb",
+        Subpixel::HorizontalBgr => "horizontal_bgr",
+        Subpixel::VerticalRgb => "vertical_rgb",
+        Subpixel::VerticalBgr => "vertical_bgr",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn nested_fields_are_flattened_in_order() {
+        let value = json!({
+            "x": 1,
+            "relative": {"x": 2, "width": 3},
+            "screen": {"name": "DP-1", "mode": {"refresh_hz": 59.95}},
+            "label": null,
+        });
+        let keys: Vec<String> = flatten(&value).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            [
+                "x",
+                "relative_x",
+                "relative_width",
+                "screen_name",
+                "screen_mode_refresh_hz",
+                "label"
+            ]
+        );
+        assert_eq!(
+            shell_assignments(&value),
+            "WAYSIP_X='1'\nWAYSIP_RELATIVE_X='2'\nWAYSIP_RELATIVE_WIDTH='3'\n\
+             WAYSIP_SCREEN_NAME='DP-1'\nWAYSIP_SCREEN_MODE_REFRESH_HZ='59.95'\nWAYSIP_LABEL=''\n"
+        );
+    }
+
+    #[test]
+    fn shell_values_survive_eval() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("ran");
+        let label = format!(
+            "it's $(touch {0}) `touch {0}` ${{HOME}} \"quoted\" \\ end\nsecond line\n",
+            marker.display()
+        );
+        let value = json!({"label": label, "screen": {"name": "DP-'1'"}});
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(r#"eval "$1"; printf '%s|%s' "$WAYSIP_LABEL" "$WAYSIP_SCREEN_NAME""#)
+            .arg("sh")
+            .arg(shell_assignments(&value))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("{label}|DP-'1'")
+        );
+        assert!(!marker.exists(), "the label ran a command");
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let value = json!({
+            "plain": "DP-1",
+            "comma": "a,b",
+            "quote": "say \"hi\"",
+            "newline": "one\ntwo",
+            "return": "one\rtwo",
+            "nested": {"empty": null, "number": 1.5},
+        });
+        assert_eq!(
+            csv_table(&value),
+            "plain,comma,quote,newline,return,nested_empty,nested_number\n\
+             DP-1,\"a,b\",\"say \"\"hi\"\"\",\"one\ntwo\",\"one\rtwo\",,1.5\n"
+        );
+    }
+}
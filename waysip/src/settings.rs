@@ -1,9 +1,25 @@
 //! Runtime behaviour derived from CLI flags.
 
-use std::io::{IsTerminal, Read};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{IsTerminal, Read},
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    path::Path,
+    time::Duration,
+};
 
-use crate::cli::Cli;
-use libwaysip::{AreaInfo, BoxInfo, Color, SelectionType, WaySip};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{BorderPosition, BoxPolicy, CardPosition, Cli, Guide, LabelCorner, StampAnchor},
+    config::{Config, Options, OutputStyle},
+};
+use libwaysip::{
+    AreaInfo, Bounds, BoxInfo, Color, CompositionGuides, DEFAULT_LABEL_FORMAT,
+    DEFAULT_SCREEN_CARD_FORMAT, SelectionType, Size, SizeLimits, Style, WaySip, error::ColorError,
+    format::Template,
+};
 
 // ─── Selection dispatch ───────────────────────────────────────────────────────
 
@@ -15,6 +31,8 @@ pub(crate) enum SelectionDispatch {
     DimensionsOrOutput,
     Area,
     Screen,
+    /// `-z`: an area of this width and height follows the pointer.
+    FixedSize(i32, i32),
 }
 
 impl SelectionDispatch {
@@ -27,6 +45,9 @@ impl SelectionDispatch {
             Some(Self::Area)
         } else if args.output || args.screen {
             Some(Self::Screen)
+        } else if let Some(size) = &args.fixed_size {
+            let Size { width, height } = parse_size(size, "-z size");
+            Some(Self::FixedSize(width, height))
         } else {
             None
         }
@@ -38,6 +59,7 @@ impl SelectionDispatch {
             Self::DimensionsOrOutput => SelectionType::DimensionsOrOutput,
             Self::Area => SelectionType::Area,
             Self::Screen => SelectionType::Screen,
+            Self::FixedSize(width, height) => SelectionType::FixedSize(width, height),
         }
     }
 }
@@ -51,6 +73,157 @@ pub(crate) fn parse_hex_color(s: String) -> Color {
     })
 }
 
+pub(crate) fn parse_border_weight(s: &str) -> f64 {
+    s.parse::<f64>().unwrap_or_else(|_| {
+        eprintln!("Invalid border weight, use -w <n> to set it");
+        std::process::exit(1);
+    })
+}
+
+/// The templates of the texts drawn on the overlay, parsed before it shows up.
+pub(crate) struct OverlayTexts {
+    pub label: Option<Template>,
+    pub screen_card: Option<Template>,
+}
+
+impl OverlayTexts {
+    pub(crate) fn from_cli(args: &Cli) -> Self {
+        let label = match args.no_label {
+            true => None,
+            false => parse_overlay_text(
+                "label",
+                args.label.as_deref().unwrap_or(DEFAULT_LABEL_FORMAT),
+            ),
+        };
+        let screen_card = parse_overlay_text(
+            "screen card",
+            args.screen_card
+                .as_deref()
+                .unwrap_or(DEFAULT_SCREEN_CARD_FORMAT),
+        );
+        Self { label, screen_card }
+    }
+}
+
+/// Parse the template of an overlay text, `None` when it is empty to hide the text.
+fn parse_overlay_text(what: &str, template: &str) -> Option<Template> {
+    if template.is_empty() {
+        return None;
+    }
+    Some(Template::parse(template).unwrap_or_else(|e| {
+        eprintln!("Err: invalid {what}: {e}");
+        std::process::exit(1);
+    }))
+}
+
+pub(crate) fn composition_guides(guides: &[Guide]) -> CompositionGuides {
+    let has = |guide| guides.contains(&guide);
+    CompositionGuides {
+        thirds: has(Guide::Thirds),
+        center: has(Guide::Center),
+        title_safe: has(Guide::TitleSafe) || has(Guide::Safe),
+        action_safe: has(Guide::ActionSafe) || has(Guide::Safe),
+    }
+}
+
+/// Cairo rejects negative dashes and patterns with nothing but gaps.
+pub(crate) fn check_border_dash(dash: Vec<f64>) -> Vec<f64> {
+    if dash.iter().any(|len| !len.is_finite() || *len < 0.0)
+        || (!dash.is_empty() && dash.iter().all(|len| *len == 0.0))
+    {
+        eprintln!("Invalid border dash, use --border-dash <length,...> with positive lengths");
+        std::process::exit(1);
+    }
+    dash
+}
+
+pub(crate) fn parse_timeout(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds).unwrap_or_else(|_| {
+        eprintln!("Invalid timeout, use --timeout <seconds> with a positive number");
+        std::process::exit(1);
+    })
+}
+
+/// The ratios of `--aspect-presets`, `None` for `free`.
+pub(crate) fn parse_aspect_presets(presets: Vec<String>) -> Vec<Option<(f64, f64)>> {
+    presets
+        .into_iter()
+        .map(|preset| match preset.trim() {
+            "free" => None,
+            ratio if ratio.contains(':') => Some(parse_aspect_ratio(ratio.to_string())),
+            _ => {
+                eprintln!(
+                    "Err: invalid aspect preset `{preset}`, expected `free` or `width:height`"
+                );
+                std::process::exit(1);
+            }
+        })
+        .collect()
+}
+
+/// The size limits of `--min-width`, `--min-height`, `--max-width` and `--max-height`, `None`
+/// without any of them.
+pub(crate) fn parse_size_limits(args: &Cli) -> Option<SizeLimits> {
+    try_size_limits(args).unwrap_or_else(|e| {
+        eprintln!("Err: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn try_size_limits(args: &Cli) -> Result<Option<SizeLimits>, &'static str> {
+    let sides = [
+        args.min_width,
+        args.min_height,
+        args.max_width,
+        args.max_height,
+    ];
+    if sides.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    if sides
+        .iter()
+        .flatten()
+        .any(|len| !len.is_finite() || *len < 0.0)
+    {
+        return Err("the size limits must be positive numbers");
+    }
+    let limits = SizeLimits {
+        min_width: args.min_width.unwrap_or(0.0),
+        min_height: args.min_height.unwrap_or(0.0),
+        max_width: args.max_width.unwrap_or(f64::INFINITY),
+        max_height: args.max_height.unwrap_or(f64::INFINITY),
+    };
+    if limits.min_width > limits.max_width || limits.min_height > limits.max_height {
+        return Err("a minimum size is larger than the maximum size");
+    }
+    Ok(Some(limits))
+}
+
+pub(crate) fn parse_bounds(s: &str) -> Bounds {
+    try_bounds(s).unwrap_or_else(|| {
+        eprintln!(
+            "Err: invalid bounds `{s}`, expected `output`, `output:NAME`, `box:LABEL` or `x,y WIDTHxHEIGHT`"
+        );
+        std::process::exit(1);
+    })
+}
+
+fn try_bounds(s: &str) -> Option<Bounds> {
+    if s == "output" {
+        return Some(Bounds::Output);
+    }
+    match s.split_once(':') {
+        Some(("output", name)) if !name.is_empty() => Some(Bounds::NamedOutput(name.to_string())),
+        Some(("box", label)) if !label.is_empty() => Some(Bounds::Box(label.to_string())),
+        _ => BoxInfo::get_box_from_str(s).ok().map(|rect| Bounds::Rect {
+            x: rect.start_x,
+            y: rect.start_y,
+            width: rect.end_x - rect.start_x,
+            height: rect.end_y - rect.start_y,
+        }),
+    }
+}
+
 pub(crate) fn parse_aspect_ratio(s: String) -> (f64, f64) {
     let parts: Vec<&str> = s.split(':').collect();
     if parts.len() != 2 {
@@ -74,8 +247,86 @@ pub(crate) fn run_selection(
     args: &mut Cli,
     sel: SelectionType,
     boxes: Option<Vec<BoxInfo>>,
+    texts: OverlayTexts,
+    outputs: &BTreeMap<String, OutputStyle>,
 ) -> AreaInfo {
-    let mut builder = WaySip::new().with_selection_type(sel);
+    let builder = waysip_from_cli(args, sel, boxes, texts, outputs);
+    match builder.get() {
+        Ok(Some(info)) => info,
+        Ok(None) => {
+            eprintln!("Selection canceled");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Show the identify cards until a key or a button is pressed, or the timeout.
+pub(crate) fn run_identify(args: &mut Cli, outputs: &BTreeMap<String, OutputStyle>) {
+    if args.timeout.is_none() {
+        args.timeout = Some(IDENTIFY_TIMEOUT);
+    }
+    let texts = OverlayTexts {
+        label: None,
+        screen_card: None,
+    };
+    let builder = waysip_from_cli(args, SelectionType::Identify, None, texts, outputs);
+    if let Err(e) = builder.get() {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Seconds before `--identify` closes by itself.
+const IDENTIFY_TIMEOUT: f64 = 5.0;
+
+/// Draw the overlay offscreen into the PNG at `path`, with the boxes from stdin with `--boxes`.
+pub(crate) fn run_preview(args: &mut Cli, path: &Path, outputs: &BTreeMap<String, OutputStyle>) {
+    let size = parse_size(&args.preview_size, "preview size");
+    let boxes = args.boxes.then(read_boxes_from_stdin);
+    let texts = OverlayTexts::from_cli(args);
+    let builder = waysip_from_cli(args, SelectionType::Area, boxes, texts, outputs);
+    let mut file = File::create(path).unwrap_or_else(|e| {
+        eprintln!("Err: cannot create `{}`: {e}", path.display());
+        std::process::exit(1);
+    });
+    if let Err(e) = builder.preview(size, &mut file) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// A `WIDTHxHEIGHT` size, `what` names it in the error.
+fn parse_size(s: &str, what: &str) -> Size {
+    let size = s.split_once('x').and_then(|(width, height)| {
+        Some((width.parse::<i32>().ok()?, height.parse::<i32>().ok()?))
+    });
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Size { width, height },
+        _ => {
+            eprintln!("Err: invalid {what} `{s}`, expected `WIDTHxHEIGHT`");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn waysip_from_cli(
+    args: &mut Cli,
+    sel: SelectionType,
+    boxes: Option<Vec<BoxInfo>>,
+    texts: OverlayTexts,
+    outputs: &BTreeMap<String, OutputStyle>,
+) -> WaySip {
+    let mut builder = WaySip::new()
+        .with_selection_type(sel)
+        .with_label(texts.label)
+        .with_screen_card(texts.screen_card);
+    if let Some(position) = args.screen_card_position.take() {
+        builder = builder.with_screen_card_position(position.into());
+    }
 
     if let Some(color) = args.background.take() {
         builder = builder.with_background_color(parse_hex_color(color));
@@ -89,12 +340,24 @@ pub(crate) fn run_selection(
     if let Some(color) = args.box_color.take() {
         builder = builder.with_box_color(parse_hex_color(color));
     }
+    if let Some(color) = args.box_hover_color.take() {
+        builder = builder.with_box_hover_color(parse_hex_color(color));
+    }
+    if let Some(policy) = args.box_policy.take() {
+        builder = builder.with_box_policy(policy.into());
+    }
     if let Some(border_weight) = args.border_weight.take() {
-        let bw = border_weight.parse::<f64>().unwrap_or_else(|_| {
-            eprintln!("Invalid border weight, use -w <n> to set it");
-            std::process::exit(1);
-        });
-        builder = builder.with_border_weight(bw);
+        builder = builder.with_border_weight(parse_border_weight(&border_weight));
+    }
+    if let Some(dash) = args.border_dash.take() {
+        builder = builder.with_border_dash(check_border_dash(dash));
+    }
+    if let Some(radius) = args.corner_radius.take() {
+        builder = builder.with_corner_radius(radius);
+    }
+    builder = builder.with_marching_ants(args.marching_ants.unwrap_or(false));
+    if let Some(position) = args.border_position.take() {
+        builder = builder.with_border_position(position.into());
     }
     if let Some(font_size) = args.font_size.take() {
         builder = builder.with_font_size(font_size);
@@ -102,40 +365,277 @@ pub(crate) fn run_selection(
     if let Some(font_name) = args.font_name.take() {
         builder = builder.with_font_name(font_name);
     }
+    if let Some(corner) = args.label_corner.take() {
+        builder = builder.with_label_corner(corner.into());
+    }
+    if let Some(color) = args.label_background.take() {
+        builder = builder.with_label_background_color(parse_hex_color(color));
+    }
+    builder = builder
+        .with_crosshair(args.crosshair.unwrap_or(false))
+        .with_rulers(args.rulers.unwrap_or(false))
+        .with_status_line(args.status_line.unwrap_or(false));
+    if let Some(guides) = args.guides.take() {
+        builder = builder.with_composition_guides(composition_guides(&guides));
+    }
     if let Some(boxes) = boxes {
         builder = builder.with_predefined_boxes(boxes);
     }
+    if let Some(fd) = open_box_stream(args) {
+        builder = builder.with_box_stream(fd, parse_box_snapshot);
+    }
     if let Some(aspect_ratio) = args.aspect_ratio.take() {
         let (width, height) = parse_aspect_ratio(aspect_ratio);
         builder = builder.with_aspect_ratio(width, height);
     }
+    if let Some(presets) = args.aspect_presets.take() {
+        builder = builder.with_aspect_presets(parse_aspect_presets(presets));
+    }
+    if let Some(anchor) = args.stamp_anchor.take() {
+        builder = builder.with_stamp_anchor(anchor.into());
+    }
+    if let Some(limits) = parse_size_limits(args) {
+        builder = builder.with_size_limits(limits);
+    }
+    if let Some(bounds) = args.bounds.take() {
+        builder = builder.with_bounds(parse_bounds(&bounds));
+    }
+    if let Some(color) = args.clamp_color.take() {
+        builder = builder.with_clamp_color(parse_hex_color(color));
+    }
+    if let Some(color) = args.inactive_color.take() {
+        builder = builder.with_inactive_color(parse_hex_color(color));
+    }
+    if args.blackout == Some(true) {
+        builder = builder.with_inactive_color(BLACKOUT);
+    }
+    if let Some(color) = args.highlight_color.take() {
+        builder = builder.with_highlight_color(parse_hex_color(color));
+    }
+    builder = builder.with_outline_hovered(args.outline_output.unwrap_or(false));
+    for (name, output) in outputs {
+        let style = output_style(builder.get_style(), output);
+        builder = builder.with_output_style(name, style);
+    }
 
-    match builder.get() {
-        Ok(Some(info)) => info,
-        Ok(None) => {
-            eprintln!("Selection canceled");
-            std::process::exit(0);
-        }
-        Err(e) => {
-            eprintln!("Error: {e}");
-            std::process::exit(1);
-        }
+    if let Some(timeout) = args.timeout.take() {
+        builder = builder.with_timeout(parse_timeout(timeout));
+    }
+    builder
+}
+
+/// The color of `--blackout`.
+const BLACKOUT: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 1.0,
+};
+
+/// The style of an output from the `[outputs]` table of the config file.
+fn output_style(base: &Style, output: &OutputStyle) -> Style {
+    let mut style = base.clone();
+    let color = |color: &Option<String>| color.clone().map(parse_hex_color);
+    if let Some(color) = color(&output.background) {
+        style.background_color = color;
+    }
+    if let Some(color) = color(&output.border_color) {
+        style.foreground_color = color;
+    }
+    if let Some(color) = color(&output.selection_color) {
+        style.border_text_color = color;
+    }
+    if let Some(color) = color(&output.box_color) {
+        style.box_color = color;
+    }
+    if let Some(color) = color(&output.inactive_color) {
+        style.inactive_color = Some(color);
+    }
+    if let Some(color) = color(&output.highlight_color) {
+        style.highlight_color = Some(color);
     }
+    if let Some(font_name) = &output.font_name {
+        style.font_name = font_name.clone();
+    }
+    if let Some(font_size) = output.font_size {
+        style.font_size = font_size;
+    }
+    if let Some(border_weight) = output.border_weight {
+        style.border_weight = border_weight;
+    }
+    style
 }
 
 // ─── Output format string ───────────────────────────────────────────────────
 
-pub(crate) fn resolve_output_format(args: &mut Cli) -> String {
+pub(crate) fn resolve_output_format(args: &mut Cli, config: &Config) -> String {
+    if let Some(name) = args.preset.take() {
+        return resolve_preset(&name, config);
+    }
     match args.screen {
         true => {
             "Screen : %o %d\nlogic_width: %w, logic_height: %h\nwidth: %L, height: %T".to_string()
         }
-        false => std::mem::take(&mut args.format),
+        false => args
+            .format
+            .take()
+            .unwrap_or_else(|| DEFAULT_FORMAT.to_string()),
     }
 }
 
+/// The `-f` format used when neither the CLI nor the config sets an output format.
+const DEFAULT_FORMAT: &str = "%x,%y %wx%h\n";
+
+// ─── Effective configuration ────────────────────────────────────────────────
+
+/// The settings in use after applying the config file, as a config file, for `--print-config`.
+pub(crate) fn effective_config(args: &Cli, config: &Config) -> String {
+    let style = Style::default();
+    let color = |arg: &Option<String>, default: Color| {
+        Some(arg.clone().unwrap_or_else(|| default.to_hex()))
+    };
+    let no_output_option =
+        args.format.is_none() && args.output_format.is_none() && args.preset.is_none();
+    let options = Options {
+        background: color(&args.background, style.background_color),
+        border_color: color(&args.border_color, style.foreground_color),
+        selection_color: color(&args.selection_color, style.border_text_color),
+        box_color: color(&args.box_color, style.box_color),
+        box_hover_color: args.box_hover_color.clone(),
+        box_policy: Some(args.box_policy.unwrap_or(BoxPolicy::Smallest)),
+        clamp_color: color(&args.clamp_color, style.clamp_color),
+        font_name: Some(args.font_name.clone().unwrap_or(style.font_name)),
+        font_size: Some(args.font_size.unwrap_or(style.font_size)),
+        border_weight: Some(
+            args.border_weight
+                .as_deref()
+                .map(parse_border_weight)
+                .unwrap_or(style.border_weight),
+        ),
+        border_dash: Some(args.border_dash.clone().unwrap_or(style.border_dash)),
+        corner_radius: Some(args.corner_radius.unwrap_or(style.corner_radius)),
+        marching_ants: Some(args.marching_ants.unwrap_or(false)),
+        inactive_color: match args.blackout {
+            Some(true) => Some(BLACKOUT.to_hex()),
+            _ => args.inactive_color.clone(),
+        },
+        blackout: None,
+        highlight_color: args.highlight_color.clone(),
+        outline_output: Some(args.outline_output.unwrap_or(false)),
+        timeout: args.timeout,
+        border_position: Some(args.border_position.unwrap_or(BorderPosition::Center)),
+        label: Some(match args.no_label {
+            true => String::new(),
+            false => args
+                .label
+                .clone()
+                .unwrap_or_else(|| DEFAULT_LABEL_FORMAT.to_string()),
+        }),
+        screen_card: Some(
+            args.screen_card
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SCREEN_CARD_FORMAT.to_string()),
+        ),
+        screen_card_position: Some(args.screen_card_position.unwrap_or(CardPosition::TopLeft)),
+        label_corner: Some(args.label_corner.unwrap_or(LabelCorner::BottomRight)),
+        label_background: color(&args.label_background, style.label_background_color),
+        crosshair: Some(args.crosshair.unwrap_or(false)),
+        rulers: Some(args.rulers.unwrap_or(false)),
+        guides: Some(args.guides.clone().unwrap_or_default()),
+        status_line: Some(args.status_line.unwrap_or(false)),
+        format: match no_output_option {
+            true => Some(DEFAULT_FORMAT.to_string()),
+            false => args.format.clone(),
+        },
+        output_format: args.output_format,
+        preset: args.preset.clone(),
+        aspect_ratio: args.aspect_ratio.clone(),
+        aspect_presets: args.aspect_presets.clone(),
+        stamp_anchor: Some(args.stamp_anchor.unwrap_or(StampAnchor::Center)),
+        min_width: args.min_width,
+        min_height: args.min_height,
+        max_width: args.max_width,
+        max_height: args.max_height,
+        bounds: args.bounds.clone(),
+        #[cfg(feature = "logger")]
+        log_level: Some(
+            args.log_level
+                .unwrap_or(tracing::Level::INFO)
+                .to_string()
+                .to_lowercase(),
+        ),
+        #[cfg(not(feature = "logger"))]
+        log_level: None,
+    };
+    #[derive(Serialize)]
+    struct Effective<'a> {
+        #[serde(flatten)]
+        options: Options,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        outputs: &'a BTreeMap<String, OutputStyle>,
+    }
+    let effective = Effective {
+        options,
+        outputs: &config.outputs,
+    };
+    toml::to_string(&effective).expect("Options are always serialisable")
+}
+
+// ─── Output presets ─────────────────────────────────────────────────────────
+
+/// Built-in `--preset` templates. Tools cropping a single output's frames get output-relative
+/// buffer pixels, tools taking a compositor region get global logical coordinates.
+const PRESETS: &[(&str, &str)] = &[
+    // grim -g "$(waysip -d --preset grim)"
+    ("grim", "%x,%y %wx%h\n"),
+    // wf-recorder -g "$(waysip -d --preset wf-recorder)"
+    ("wf-recorder", "%x,%y %wx%h\n"),
+    // ffmpeg -vf "$(waysip -d --preset ffmpeg)" on a capture of the selected output
+    ("ffmpeg", "crop=%pw:%ph:%px:%py\n"),
+    // gpu-screen-recorder -w region -region "$(waysip -d --preset gpu-screen-recorder)"
+    ("gpu-screen-recorder", "%wx%h+%x+%y\n"),
+    // magick shot.png -crop "$(waysip -d --preset imagemagick)" on a shot of the selected output
+    ("imagemagick", "%pwx%ph+%px+%py\n"),
+];
+
+/// Find the template of a preset, the ones from the config file first.
+fn resolve_preset(name: &str, config: &Config) -> String {
+    if let Some(template) = config.presets.get(name) {
+        return template.clone();
+    }
+    if let Some((_, template)) = PRESETS.iter().find(|(preset, _)| *preset == name) {
+        return template.to_string();
+    }
+    let names: Vec<&str> = PRESETS
+        .iter()
+        .map(|(preset, _)| *preset)
+        .chain(config.presets.keys().map(String::as_str))
+        .collect();
+    eprintln!(
+        "Unknown preset `{name}`, available presets: {}",
+        names.join(", ")
+    );
+    std::process::exit(1);
+}
+
 // ─── Predefined boxes (stdin) ─────────────────────────────────────────────────
 
+/// The predefined boxes of `-r` or `--windows`, `None` without them.
+pub(crate) fn read_boxes(args: &Cli) -> Option<Vec<BoxInfo>> {
+    #[cfg(feature = "providers")]
+    if args.windows {
+        return Some(crate::providers::list_windows());
+    }
+    if !args.boxes {
+        return None;
+    }
+    // the stream brings the boxes while the overlay is shown
+    if args.box_stream || args.box_fd.is_some() {
+        return Some(Vec::new());
+    }
+    Some(read_boxes_from_stdin())
+}
+
 pub(crate) fn read_boxes_from_stdin() -> Vec<BoxInfo> {
     let mut stdio = std::io::stdin();
     if stdio.is_terminal() {
@@ -154,8 +654,13 @@ pub(crate) fn read_boxes_from_stdin() -> Vec<BoxInfo> {
         std::process::exit(1);
     }
 
+    if input_string.trim_start().starts_with('[') {
+        return read_json_boxes(&input_string);
+    }
+
     input_string
         .lines()
+        .filter(|line| !line.trim().is_empty())
         .map(BoxInfo::get_box_from_str)
         .collect::<Result<Vec<_>, _>>()
         .unwrap_or_else(|e| {
@@ -163,3 +668,172 @@ pub(crate) fn read_boxes_from_stdin() -> Vec<BoxInfo> {
             std::process::exit(1);
         })
 }
+
+/// A predefined box of the JSON input.
+#[derive(Deserialize)]
+struct BoxRecord {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    z: i32,
+}
+
+impl BoxRecord {
+    fn into_box_info(self) -> Result<BoxInfo, ColorError> {
+        let mut info = BoxInfo::new(self.x, self.y, self.x + self.width, self.y + self.height);
+        info.label = self.label;
+        info.color = self.color.map(Color::hex_to_color).transpose()?;
+        info.z = self.z;
+        Ok(info)
+    }
+}
+
+fn read_json_boxes(input: &str) -> Vec<BoxInfo> {
+    let records: Vec<BoxRecord> = serde_json::from_str(input).unwrap_or_else(|e| {
+        eprintln!("Err: invalid JSON boxes: {e}");
+        std::process::exit(1);
+    });
+    records
+        .into_iter()
+        .map(BoxRecord::into_box_info)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| {
+            eprintln!("Err: {e}");
+            std::process::exit(1);
+        })
+}
+
+// ─── Predefined boxes (stream) ────────────────────────────────────────────────
+
+/// The fd of `--box-fd`, or stdin with `--box-stream`.
+fn open_box_stream(args: &Cli) -> Option<OwnedFd> {
+    if let Some(fd) = args.box_fd {
+        // duplicated rather than adopted, so that a wrong fd is reported instead of closed, and
+        // rather than reopened from /dev/fd, which fails for sockets
+        // SAFETY: the fd is only borrowed for the check and the duplication, which both fail
+        // cleanly when it is not open
+        let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+        let owned = rustix::fs::fstat(borrowed)
+            .and_then(|_| rustix::io::fcntl_dupfd_cloexec(borrowed, 0))
+            .unwrap_or_else(|e| {
+                eprintln!("Err: cannot read boxes from fd {fd}: {e}");
+                std::process::exit(1);
+            });
+        return Some(owned);
+    }
+    if !args.box_stream {
+        return None;
+    }
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprintln!("No piped stdin, please pipe a stream of boxes to stdin");
+        std::process::exit(1);
+    }
+    Some(
+        stdin
+            .as_fd()
+            .try_clone_to_owned()
+            .expect("Failed to read stdin"),
+    )
+}
+
+/// Parse a line of the box stream, a JSON array of boxes. Invalid lines are skipped.
+fn parse_box_snapshot(line: &str) -> Option<Vec<BoxInfo>> {
+    let records: Vec<BoxRecord> = serde_json::from_str(line)
+        .inspect_err(|e| tracing::warn!("Skipping invalid box snapshot: {e}"))
+        .ok()?;
+    records
+        .into_iter()
+        .map(BoxRecord::into_box_info)
+        .collect::<Result<Vec<_>, _>>()
+        .inspect_err(|e| tracing::warn!("Skipping invalid box snapshot: {e}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tests::parse;
+
+    fn limits(args: &[&str], env: &[(&str, &str)]) -> Result<Option<SizeLimits>, &'static str> {
+        try_size_limits(&parse(args, env).unwrap())
+    }
+
+    #[test]
+    fn size_limits() {
+        assert_eq!(limits(&[], &[]), Ok(None));
+        assert_eq!(
+            limits(&["--min-width", "10", "--max-height", "50.5"], &[]),
+            Ok(Some(SizeLimits {
+                min_width: 10.0,
+                max_height: 50.5,
+                ..SizeLimits::default()
+            }))
+        );
+        let env = [("WAYSIP_MIN_HEIGHT", "20"), ("WAYSIP_MAX_HEIGHT", "40")];
+        let limits_of_env = limits(&["--max-height", "30"], &env).unwrap().unwrap();
+        assert_eq!(
+            [limits_of_env.min_height, limits_of_env.max_height],
+            [20.0, 30.0]
+        );
+        // a side may be both its minimum and its maximum
+        assert!(limits(&["--min-width", "10", "--max-width", "10"], &[]).is_ok());
+
+        // min > max, also across the command line and the environment
+        assert!(limits(&["--min-width", "20", "--max-width", "10"], &[]).is_err());
+        assert!(limits(&["--min-height", "50"], &env).is_err());
+        // malformed
+        for value in ["-1", "inf", "NaN"] {
+            let arg = format!("--max-width={value}");
+            assert!(limits(&[&arg], &[]).is_err(), "{value}");
+        }
+        assert!(parse(&["--min-width", "wide"], &[]).is_err());
+        assert!(parse(&[], &[("WAYSIP_MAX_WIDTH", "10px")]).is_err());
+    }
+
+    #[test]
+    fn bounds() {
+        assert_eq!(try_bounds("output"), Some(Bounds::Output));
+        assert_eq!(
+            try_bounds("output:DP-1"),
+            Some(Bounds::NamedOutput("DP-1".to_string()))
+        );
+        assert_eq!(
+            try_bounds("box:Firefox: web"),
+            Some(Bounds::Box("Firefox: web".to_string()))
+        );
+        assert_eq!(
+            try_bounds("10,-20 300x200"),
+            Some(Bounds::Rect {
+                x: 10.0,
+                y: -20.0,
+                width: 300.0,
+                height: 200.0
+            })
+        );
+        let cli = parse(&[], &[("WAYSIP_BOUNDS", "box:kitty")]).unwrap();
+        assert_eq!(cli.bounds.as_deref(), Some("box:kitty"));
+
+        // malformed
+        for s in [
+            "",
+            "outputs",
+            "output:",
+            "box:",
+            "screen:DP-1",
+            "10,20",
+            "10 20x30",
+            "10,20 30",
+            "a,0 1x1",
+            "nan,0 1x1",
+        ] {
+            assert_eq!(try_bounds(s), None, "{s:?}");
+        }
+    }
+}